@@ -1,27 +1,121 @@
 //! Code generator for use in `build.rs`.
 
 use std::{
-    io::prelude::*,
-    fs::File,
+    collections::HashSet,
     env,
-    path::PathBuf,
     error::Error,
+    fs::File,
+    io::prelude::*,
+    path::PathBuf,
+};
+
+use rubble::{
+    att::AttUuid,
+    gatt::{
+        characteristic::{Appearance, Characteristic, Properties},
+        service::{AllowedType, GapService, ServiceSpec},
+    },
 };
 
 pub type BoxedError = Box<dyn Error + Send + Sync>;
 
+const PRIMARY_SERVICE: AttUuid = AttUuid::Uuid16(0x2800);
+const SECONDARY_SERVICE: AttUuid = AttUuid::Uuid16(0x2801);
+const CHARACTERISTIC: AttUuid = AttUuid::Uuid16(0x2803);
+const CLIENT_CHARACTERISTIC_CONFIGURATION: AttUuid = AttUuid::Uuid16(0x2902);
+
+const GAP_SERVICE_UUID: AttUuid = AttUuid::Uuid16(0x1800);
+const DEVICE_NAME_UUID: AttUuid = AttUuid::Uuid16(0x2a00);
+const APPEARANCE_UUID: AttUuid = AttUuid::Uuid16(0x2a01);
+
 /// Builder for attribute sets.
-#[derive(Default)]
-pub struct Builder {}
+pub struct Builder<'a> {
+    gap: Option<GapService<'a>>,
+    services: Vec<ServiceEntry>,
+    singletons_seen: HashSet<RawUuid>,
+}
 
-impl Builder {
-    /// Creates a new builder that will produce a minimal GATT server.
+struct ServiceEntry {
+    uuid: AttUuid,
+    allowed_type: AllowedType,
+    characteristics: Vec<Characteristic>,
+}
+
+/// A hashable stand-in for [`AttUuid`] (which isn't `Hash`), used to reject duplicate
+/// registrations of a `SINGLETON` service.
+#[derive(PartialEq, Eq, Hash)]
+enum RawUuid {
+    U16(u16),
+    U32(u32),
+    U128([u8; 16]),
+}
+
+impl From<AttUuid> for RawUuid {
+    fn from(uuid: AttUuid) -> Self {
+        match uuid {
+            AttUuid::Uuid16(v) => RawUuid::U16(v),
+            AttUuid::Uuid32(v) => RawUuid::U32(v),
+            AttUuid::Uuid128(v) => RawUuid::U128(v),
+        }
+    }
+}
+
+impl<'a> Default for Builder<'a> {
+    fn default() -> Self {
+        Self {
+            gap: None,
+            services: Vec::new(),
+            singletons_seen: HashSet::new(),
+        }
+    }
+}
+
+impl<'a> Builder<'a> {
+    /// Creates a new, empty builder.
     ///
-    /// The minimal GATT server contains only a GAP service, which is mandatory for BLE devices.
+    /// At least the mandatory GAP service must be configured via [`Builder::gap`] before calling
+    /// [`Builder::build`] or [`Builder::try_build`].
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Configures the mandatory GAP service from a device name and [`Appearance`].
+    pub fn gap(mut self, device_name: &'a str, appearance: Appearance) -> Self {
+        self.gap = Some(GapService::new(device_name, appearance));
+        self
+    }
+
+    /// Registers a custom service described by a [`ServiceSpec`] implementation.
+    ///
+    /// Returns an error if `S::UUID` is a 16-bit UUID outside of the SIG-assigned GATT service
+    /// range or a 32-bit UUID, or if `S::SINGLETON` is set and a service with the same UUID was
+    /// already registered.
+    pub fn register_service<S: ServiceSpec>(mut self) -> Result<Self, BoxedError> {
+        if !S::UUID.is_sig_assigned_service_uuid() {
+            return Err(format!(
+                "service UUID {:?} is neither a 16-bit UUID in the SIG-assigned service range \
+                 (0x1800..=0x18FF) nor a 128-bit UUID; custom services must use a 128-bit UUID",
+                S::UUID
+            )
+            .into());
+        }
+
+        if S::SINGLETON && !self.singletons_seen.insert(S::UUID.into()) {
+            return Err(format!(
+                "service {:?} is marked SINGLETON but was registered more than once",
+                S::UUID
+            )
+            .into());
+        }
+
+        self.services.push(ServiceEntry {
+            uuid: S::UUID,
+            allowed_type: S::ALLOWED_TYPE,
+            characteristics: S::characteristics().collect(),
+        });
+        Ok(self)
+    }
+
     /// Generates Rust code and writes it to a file in the target directory.
     ///
     /// The file can be included into the main crate by calling the macro
@@ -31,12 +125,374 @@ impl Builder {
     }
 
     pub fn try_build(self) -> Result<(), BoxedError> {
+        let attributes = self.generate_attributes()?;
+
         let mut path = PathBuf::from(env::var_os("OUT_DIR").unwrap());
         path.push("rubble_codegen.rs");
         let mut file = File::create(path)?;
-        writeln!(file, "oops")?;
+
+        writeln!(file, "// Generated by rubble-codegen. Do not edit by hand.")?;
+        writeln!(
+            file,
+            "pub const ATTRIBUTES: &[rubble::att::GeneratedAttribute] = &["
+        )?;
+        for attribute in &attributes {
+            writeln!(file, "    {},", render_attribute(attribute))?;
+        }
+        writeln!(file, "];")?;
 
         println!("cargo:rerun-if-changed=build.rs");
         Ok(())
     }
+
+    /// Walks the GAP service and all registered [`ServiceSpec`]s, assigning monotonically
+    /// increasing 16-bit attribute handles and producing the flat attribute table that backs the
+    /// generated `ATTRIBUTES` array.
+    fn generate_attributes(&self) -> Result<Vec<Entry>, BoxedError> {
+        let gap = self
+            .gap
+            .as_ref()
+            .ok_or("a GAP service is mandatory; call `Builder::gap` before building")?;
+
+        let mut attributes = Vec::new();
+        let mut next_handle: u16 = 1;
+
+        let gap_value_handles = push_service(
+            &mut attributes,
+            &mut next_handle,
+            PRIMARY_SERVICE,
+            GAP_SERVICE_UUID,
+            &[
+                Characteristic::new(DEVICE_NAME_UUID, Properties::READ),
+                Characteristic::new(APPEARANCE_UUID, Properties::READ),
+            ],
+        );
+
+        for service in &self.services {
+            let decl_type = match service.allowed_type {
+                AllowedType::Secondary => SECONDARY_SERVICE,
+                AllowedType::Primary | AllowedType::Any => PRIMARY_SERVICE,
+            };
+            push_service(
+                &mut attributes,
+                &mut next_handle,
+                decl_type,
+                service.uuid,
+                &service.characteristics,
+            );
+        }
+
+        // Fill in the GAP service's two characteristic values now that their handles have been
+        // assigned. These are found by the handles `push_service` just returned for the GAP push
+        // above, not by matching on the Device Name/Appearance UUIDs: a custom `ServiceSpec` is
+        // free to reuse those same UUIDs in its own service, and matching by UUID would overwrite
+        // that characteristic's default value instead.
+        let device_name_handle = gap_value_handles[0];
+        let appearance_handle = gap_value_handles[1];
+        for attribute in &mut attributes {
+            if attribute.handle == device_name_handle {
+                if let EntryValue::CharacteristicValue { default, .. } = &mut attribute.value {
+                    *default = gap.device_name().as_bytes().to_vec();
+                }
+            } else if attribute.handle == appearance_handle {
+                if let EntryValue::CharacteristicValue { default, .. } = &mut attribute.value {
+                    *default = gap.appearance().as_u16().to_le_bytes().to_vec();
+                }
+            }
+        }
+
+        Ok(attributes)
+    }
+}
+
+struct Entry {
+    handle: u16,
+    att_type: AttUuid,
+    value: EntryValue,
+}
+
+enum EntryValue {
+    ServiceDeclaration(AttUuid),
+    CharacteristicDeclaration {
+        properties: u8,
+        value_handle: u16,
+        uuid: AttUuid,
+    },
+    CharacteristicValue {
+        uuid: AttUuid,
+        default: Vec<u8>,
+    },
+    ClientCharacteristicConfiguration,
+}
+
+fn alloc_handle(next_handle: &mut u16) -> u16 {
+    let handle = *next_handle;
+    *next_handle += 1;
+    handle
+}
+
+/// Pushes one service declaration and its characteristics onto `attributes`.
+///
+/// Returns the value handle allocated for each entry of `characteristics`, in the same order, so
+/// callers that need to fill in a characteristic's value after the fact (e.g. the GAP service's
+/// device name/appearance) can address it by handle instead of by UUID.
+fn push_service(
+    attributes: &mut Vec<Entry>,
+    next_handle: &mut u16,
+    decl_type: AttUuid,
+    service_uuid: AttUuid,
+    characteristics: &[Characteristic],
+) -> Vec<u16> {
+    attributes.push(Entry {
+        handle: alloc_handle(next_handle),
+        att_type: decl_type,
+        value: EntryValue::ServiceDeclaration(service_uuid),
+    });
+
+    let mut value_handles = Vec::with_capacity(characteristics.len());
+    for characteristic in characteristics {
+        let decl_handle = alloc_handle(next_handle);
+        let value_handle = alloc_handle(next_handle);
+        value_handles.push(value_handle);
+
+        attributes.push(Entry {
+            handle: decl_handle,
+            att_type: CHARACTERISTIC,
+            value: EntryValue::CharacteristicDeclaration {
+                properties: characteristic.properties.as_u8(),
+                value_handle,
+                uuid: characteristic.uuid,
+            },
+        });
+        attributes.push(Entry {
+            handle: value_handle,
+            att_type: characteristic.uuid,
+            value: EntryValue::CharacteristicValue {
+                uuid: characteristic.uuid,
+                default: Vec::new(),
+            },
+        });
+
+        if characteristic.properties.needs_cccd() {
+            attributes.push(Entry {
+                handle: alloc_handle(next_handle),
+                att_type: CLIENT_CHARACTERISTIC_CONFIGURATION,
+                value: EntryValue::ClientCharacteristicConfiguration,
+            });
+        }
+    }
+
+    value_handles
+}
+
+fn render_attribute(entry: &Entry) -> String {
+    format!(
+        "rubble::att::GeneratedAttribute {{ handle: {}, att_type: {}, value: {} }}",
+        entry.handle,
+        render_uuid(entry.att_type),
+        render_value(&entry.value),
+    )
+}
+
+fn render_uuid(uuid: AttUuid) -> String {
+    match uuid {
+        AttUuid::Uuid16(v) => format!("rubble::att::AttUuid::Uuid16(0x{:04x})", v),
+        AttUuid::Uuid32(v) => format!("rubble::att::AttUuid::Uuid32(0x{:08x})", v),
+        AttUuid::Uuid128(bytes) => format!(
+            "rubble::att::AttUuid::Uuid128([{}])",
+            bytes
+                .iter()
+                .map(|b| format!("0x{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn render_value(value: &EntryValue) -> String {
+    match value {
+        EntryValue::ServiceDeclaration(uuid) => format!(
+            "rubble::att::GeneratedValue::ServiceDeclaration({})",
+            render_uuid(*uuid)
+        ),
+        EntryValue::CharacteristicDeclaration {
+            properties,
+            value_handle,
+            uuid,
+        } => format!(
+            "rubble::att::GeneratedValue::CharacteristicDeclaration {{ properties: 0x{:02x}, value_handle: {}, uuid: {} }}",
+            properties,
+            value_handle,
+            render_uuid(*uuid),
+        ),
+        EntryValue::CharacteristicValue { uuid, default } => format!(
+            "rubble::att::GeneratedValue::CharacteristicValue {{ uuid: {}, default: {} }}",
+            render_uuid(*uuid),
+            render_default(default),
+        ),
+        EntryValue::ClientCharacteristicConfiguration => {
+            "rubble::att::GeneratedValue::ClientCharacteristicConfiguration".to_string()
+        }
+    }
+}
+
+fn render_default(default: &[u8]) -> String {
+    if default.is_empty() {
+        "None".to_string()
+    } else {
+        format!(
+            "Some(&[{}])",
+            default
+                .iter()
+                .map(|b| format!("0x{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct OutOfRangeService;
+    impl ServiceSpec for OutOfRangeService {
+        const ALLOWED_TYPE: AllowedType = AllowedType::Primary;
+        const UUID: AttUuid = AttUuid::Uuid16(0x1234);
+        const SINGLETON: bool = false;
+        type Characteristics = std::vec::IntoIter<Characteristic>;
+        fn characteristics() -> Self::Characteristics {
+            Vec::new().into_iter()
+        }
+    }
+
+    struct InRangeService;
+    impl ServiceSpec for InRangeService {
+        const ALLOWED_TYPE: AllowedType = AllowedType::Primary;
+        const UUID: AttUuid = AttUuid::Uuid16(0x1811);
+        const SINGLETON: bool = false;
+        type Characteristics = std::vec::IntoIter<Characteristic>;
+        fn characteristics() -> Self::Characteristics {
+            vec![Characteristic::new(AttUuid::Uuid16(0x2a19), Properties::READ)].into_iter()
+        }
+    }
+
+    struct SingletonService;
+    impl ServiceSpec for SingletonService {
+        const ALLOWED_TYPE: AllowedType = AllowedType::Primary;
+        const UUID: AttUuid = AttUuid::Uuid16(0x1812);
+        const SINGLETON: bool = true;
+        type Characteristics = std::vec::IntoIter<Characteristic>;
+        fn characteristics() -> Self::Characteristics {
+            Vec::new().into_iter()
+        }
+    }
+
+    struct NotifyingService;
+    impl ServiceSpec for NotifyingService {
+        const ALLOWED_TYPE: AllowedType = AllowedType::Primary;
+        const UUID: AttUuid = AttUuid::Uuid16(0x1813);
+        const SINGLETON: bool = false;
+        type Characteristics = std::vec::IntoIter<Characteristic>;
+        fn characteristics() -> Self::Characteristics {
+            vec![Characteristic::new(
+                AttUuid::Uuid16(0x2a99),
+                Properties::READ | Properties::NOTIFY,
+            )]
+            .into_iter()
+        }
+    }
+
+    struct ReusesDeviceNameUuidService;
+    impl ServiceSpec for ReusesDeviceNameUuidService {
+        const ALLOWED_TYPE: AllowedType = AllowedType::Primary;
+        const UUID: AttUuid = AttUuid::Uuid16(0x1814);
+        const SINGLETON: bool = false;
+        type Characteristics = std::vec::IntoIter<Characteristic>;
+        fn characteristics() -> Self::Characteristics {
+            vec![Characteristic::new(DEVICE_NAME_UUID, Properties::READ)].into_iter()
+        }
+    }
+
+    #[test]
+    fn register_service_rejects_uuid_outside_sig_range() {
+        assert!(Builder::new().register_service::<OutOfRangeService>().is_err());
+    }
+
+    #[test]
+    fn register_service_accepts_sig_assigned_uuid() {
+        assert!(Builder::new().register_service::<InRangeService>().is_ok());
+    }
+
+    #[test]
+    fn register_service_rejects_duplicate_singleton() {
+        let builder = Builder::new()
+            .register_service::<SingletonService>()
+            .unwrap();
+        assert!(builder.register_service::<SingletonService>().is_err());
+    }
+
+    #[test]
+    fn generate_attributes_assigns_sequential_handles_and_orders_cccd_after_value() {
+        let builder = Builder::new()
+            .gap("test device", Appearance::GenericSensor)
+            .register_service::<NotifyingService>()
+            .unwrap();
+
+        let attributes = builder.generate_attributes().unwrap();
+
+        // Handles are assigned sequentially starting at 1, with no gaps.
+        for (i, attribute) in attributes.iter().enumerate() {
+            assert_eq!(attribute.handle, (i + 1) as u16);
+        }
+
+        // GAP: service decl + (decl, value) * 2 characteristics = 5 attributes. Followed by
+        // NotifyingService: service decl + decl + value + CCCD = 4 attributes.
+        assert_eq!(attributes.len(), 9);
+        let notifying = &attributes[5..9];
+        assert!(matches!(notifying[0].value, EntryValue::ServiceDeclaration(_)));
+        match &notifying[1].value {
+            EntryValue::CharacteristicDeclaration { value_handle, .. } => {
+                assert_eq!(*value_handle, notifying[2].handle);
+            }
+            _ => panic!("expected a characteristic declaration"),
+        }
+        assert!(matches!(
+            notifying[2].value,
+            EntryValue::CharacteristicValue { .. }
+        ));
+        assert!(matches!(
+            notifying[3].value,
+            EntryValue::ClientCharacteristicConfiguration
+        ));
+    }
+
+    #[test]
+    fn generate_attributes_fills_in_gap_values_by_handle_not_uuid() {
+        let builder = Builder::new()
+            .gap("my device", Appearance::GenericWatch)
+            .register_service::<ReusesDeviceNameUuidService>()
+            .unwrap();
+        let attributes = builder.generate_attributes().unwrap();
+
+        let mut with_device_name_uuid = attributes.iter().filter(|a| {
+            matches!(&a.value, EntryValue::CharacteristicValue { uuid, .. } if *uuid == DEVICE_NAME_UUID)
+        });
+        // The GAP service comes first, so its Device Name value is the first match.
+        let device_name = with_device_name_uuid.next().unwrap();
+        match &device_name.value {
+            EntryValue::CharacteristicValue { default, .. } => {
+                assert_eq!(default.as_slice(), b"my device");
+            }
+            _ => unreachable!(),
+        }
+        // The custom service's characteristic with the same UUID keeps its empty default.
+        let reused = with_device_name_uuid.next().unwrap();
+        match &reused.value {
+            EntryValue::CharacteristicValue { default, .. } => {
+                assert!(default.is_empty());
+            }
+            _ => unreachable!(),
+        }
+    }
 }
@@ -0,0 +1,169 @@
+//! Attribute Protocol (ATT) fundamentals shared by the GATT server, client, and code generator.
+
+use crate::{
+    bytes::{ByteReader, ByteWriter, FromBytes, ToBytes},
+    Error,
+};
+
+/// The `ATT_MTU` in effect on a bearer until the *Exchange MTU* procedure completes.
+pub const ATT_DEFAULT_MTU: u16 = 23;
+
+/// The largest `ATT_MTU` this implementation will negotiate via *Exchange MTU* on the unenhanced
+/// ATT bearer.
+///
+/// Outgoing ATT PDUs are queued with a one-byte size that includes the 4-byte L2CAP header, so
+/// this can't be raised above 251 without also widening that size.
+pub const ATT_MAX_MTU: u16 = 247;
+
+/// `ATT_ERROR_RSP` opcode.
+pub const OPCODE_ERROR_RESPONSE: u8 = 0x01;
+/// `ATT_EXCHANGE_MTU_REQ` opcode.
+pub const OPCODE_EXCHANGE_MTU_REQUEST: u8 = 0x02;
+/// `ATT_EXCHANGE_MTU_RSP` opcode.
+pub const OPCODE_EXCHANGE_MTU_RESPONSE: u8 = 0x03;
+
+/// Error code used in an `ATT_ERROR_RSP` when a peer attempts *Exchange MTU* a second time.
+///
+/// The procedure may only be performed once per bearer; the Bluetooth spec has no dedicated error
+/// code for this case, so `Request Not Supported` is used, as recommended by the Core Spec.
+pub const ERROR_REQUEST_NOT_SUPPORTED: u8 = 0x06;
+
+/// A minimal parsed ATT PDU, covering only the opcodes the L2CAP layer needs to act on itself.
+///
+/// Everything else arrives and leaves as an opaque byte slice, routed through
+/// [`ChannelMapper::on_att_pdu`](crate::l2cap::ChannelMapper::on_att_pdu) to the GATT server/client
+/// built on top of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttPdu {
+    /// `ATT_ERROR_RSP` - a request could not be performed.
+    ErrorResponse {
+        request_opcode: u8,
+        attribute_handle: u16,
+        error_code: u8,
+    },
+    /// `ATT_EXCHANGE_MTU_REQ` - the client's proposed receive MTU.
+    ExchangeMtuRequest { client_rx_mtu: u16 },
+    /// `ATT_EXCHANGE_MTU_RSP` - the server's receive MTU, sent in answer to a request.
+    ExchangeMtuResponse { server_rx_mtu: u16 },
+}
+
+impl AttPdu {
+    /// The ATT opcode identifying this PDU.
+    pub fn opcode(&self) -> u8 {
+        match self {
+            AttPdu::ErrorResponse { .. } => OPCODE_ERROR_RESPONSE,
+            AttPdu::ExchangeMtuRequest { .. } => OPCODE_EXCHANGE_MTU_REQUEST,
+            AttPdu::ExchangeMtuResponse { .. } => OPCODE_EXCHANGE_MTU_RESPONSE,
+        }
+    }
+}
+
+impl<'a> FromBytes<'a> for AttPdu {
+    fn from_bytes(reader: &mut ByteReader<'a>) -> Result<Self, Error> {
+        let opcode = reader.read_u8()?;
+        Ok(match opcode {
+            OPCODE_ERROR_RESPONSE => AttPdu::ErrorResponse {
+                request_opcode: reader.read_u8()?,
+                attribute_handle: reader.read_u16_le()?,
+                error_code: reader.read_u8()?,
+            },
+            OPCODE_EXCHANGE_MTU_REQUEST => AttPdu::ExchangeMtuRequest {
+                client_rx_mtu: reader.read_u16_le()?,
+            },
+            OPCODE_EXCHANGE_MTU_RESPONSE => AttPdu::ExchangeMtuResponse {
+                server_rx_mtu: reader.read_u16_le()?,
+            },
+            _ => return Err(Error::InvalidValue),
+        })
+    }
+}
+
+impl ToBytes for AttPdu {
+    fn to_bytes(&self, writer: &mut ByteWriter<'_>) -> Result<(), Error> {
+        writer.write_u8(self.opcode())?;
+        match self {
+            AttPdu::ErrorResponse {
+                request_opcode,
+                attribute_handle,
+                error_code,
+            } => {
+                writer.write_u8(*request_opcode)?;
+                writer.write_u16_le(*attribute_handle)?;
+                writer.write_u8(*error_code)
+            }
+            AttPdu::ExchangeMtuRequest { client_rx_mtu } => writer.write_u16_le(*client_rx_mtu),
+            AttPdu::ExchangeMtuResponse { server_rx_mtu } => writer.write_u16_le(*server_rx_mtu),
+        }
+    }
+
+    fn encoded_size(&self) -> u8 {
+        1 + match self {
+            AttPdu::ErrorResponse { .. } => 4,
+            AttPdu::ExchangeMtuRequest { .. } | AttPdu::ExchangeMtuResponse { .. } => 2,
+        }
+    }
+}
+
+/// A 16-, 32-, or 128-bit UUID as used by the Attribute Protocol.
+///
+/// GATT reuses the Bluetooth Base UUID scheme: 16- and 32-bit UUIDs are shorthand for a UUID
+/// derived from the Base UUID, while 128-bit UUIDs can be arbitrary vendor-specific values.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AttUuid {
+    Uuid16(u16),
+    Uuid32(u32),
+    Uuid128([u8; 16]),
+}
+
+impl AttUuid {
+    /// Returns `true` if this UUID may be used as a GATT service UUID.
+    ///
+    /// 16-bit service UUIDs must fall inside the block of *Assigned Numbers* the Bluetooth SIG
+    /// has published for GATT services (`0x1800`..=`0x18FF`); anything outside of that range was
+    /// never handed out by the SIG and is almost certainly a mistake. 128-bit UUIDs have no such
+    /// restriction, since they are how custom, non-standard services are meant to be identified.
+    pub fn is_sig_assigned_service_uuid(&self) -> bool {
+        match self {
+            AttUuid::Uuid16(raw) => (0x1800..=0x18FF).contains(raw),
+            AttUuid::Uuid32(_) => false,
+            AttUuid::Uuid128(_) => true,
+        }
+    }
+}
+
+/// A single entry in a flat GATT attribute table, as produced by `rubble-codegen`.
+///
+/// This is the `'static` description emitted into `rubble_codegen.rs` and pulled into the main
+/// crate via `include_attributes!`. It only carries what can be known at build time (handles,
+/// types, and fixed default values) — the actual attribute server that serves reads and writes at
+/// runtime is built from this table by application code.
+#[derive(Copy, Clone, Debug)]
+pub struct GeneratedAttribute {
+    pub handle: u16,
+    pub att_type: AttUuid,
+    pub value: GeneratedValue,
+}
+
+/// The kind and build-time-known content of a [`GeneratedAttribute`].
+#[derive(Copy, Clone, Debug)]
+pub enum GeneratedValue {
+    /// A `Primary Service` or `Secondary Service` declaration, naming the service's UUID.
+    ServiceDeclaration(AttUuid),
+    /// A `Characteristic` declaration, pointing at the characteristic's value attribute.
+    CharacteristicDeclaration {
+        properties: u8,
+        value_handle: u16,
+        uuid: AttUuid,
+    },
+    /// A characteristic's value attribute.
+    ///
+    /// `default` holds the bytes to initialize the value with, if known at build time (e.g. a
+    /// fixed device name); otherwise the application is responsible for supplying the value at
+    /// runtime.
+    CharacteristicValue {
+        uuid: AttUuid,
+        default: Option<&'static [u8]>,
+    },
+    /// A Client Characteristic Configuration Descriptor, enabling notifications/indications.
+    ClientCharacteristicConfiguration,
+}
@@ -0,0 +1,99 @@
+//! Minimal, `no_std`-friendly byte (de)serialization used by PDU encoders and decoders.
+
+use crate::Error;
+
+/// Trait for types that can be serialized into a [`ByteWriter`].
+pub trait ToBytes {
+    /// Writes `self` into `writer`, in wire format.
+    fn to_bytes(&self, writer: &mut ByteWriter<'_>) -> Result<(), Error>;
+
+    /// The number of bytes `to_bytes` will write.
+    fn encoded_size(&self) -> u8;
+}
+
+/// Trait for types that can be parsed out of a [`ByteReader`].
+pub trait FromBytes<'a>: Sized {
+    /// Parses `Self` from the front of `reader`.
+    fn from_bytes(reader: &mut ByteReader<'a>) -> Result<Self, Error>;
+}
+
+/// A cursor over a mutable byte slice that PDU encoders write into.
+pub struct ByteWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> ByteWriter<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn write_u8(&mut self, value: u8) -> Result<(), Error> {
+        self.write_slice(&[value])
+    }
+
+    pub fn write_u16_le(&mut self, value: u16) -> Result<(), Error> {
+        self.write_slice(&value.to_le_bytes())
+    }
+
+    pub fn write_slice(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.space_left() < data.len() {
+            return Err(Error::Eof);
+        }
+        self.buf[self.pos..self.pos + data.len()].copy_from_slice(data);
+        self.pos += data.len();
+        Ok(())
+    }
+
+    /// The number of bytes still available in the underlying buffer.
+    pub fn space_left(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    /// The number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.pos
+    }
+}
+
+/// A cursor over an immutable byte slice that PDU decoders read from.
+pub struct ByteReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, Error> {
+        let byte = *self.buf.get(self.pos).ok_or(Error::Eof)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    pub fn read_u16_le(&mut self) -> Result<u16, Error> {
+        let lo = self.read_u8()? as u16;
+        let hi = self.read_u8()? as u16;
+        Ok(lo | (hi << 8))
+    }
+
+    pub fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.buf.len() - self.pos < len {
+            return Err(Error::Eof);
+        }
+        let slice = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    /// Returns all bytes that have not yet been read.
+    pub fn rest(&self) -> &'a [u8] {
+        &self.buf[self.pos..]
+    }
+
+    pub fn bytes_left(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+}
@@ -0,0 +1,21 @@
+//! Ties together the application-supplied types a BLE stack instance is built from.
+
+use crate::{
+    l2cap::ChannelMapper,
+    link::queue::{Consumer, Producer},
+};
+
+/// Configures the concrete types used by a Rubble stack instance.
+///
+/// Implemented once by the application (or a board support crate) and threaded through
+/// [`Responder`](crate::link::responder::Responder), [`Connection`](crate::link::Connection), and
+/// [`L2CAPState`](crate::l2cap::L2CAPState).
+pub trait Config: Sized {
+    /// Producer half of the packet queue used to send Data Channel PDUs.
+    type PacketProducer: Producer;
+    /// Consumer half of the packet queue used to receive Data Channel PDUs.
+    type PacketConsumer: Consumer;
+    /// Maps incoming L2CAP traffic outside of the channels L2CAP handles itself to application
+    /// state (eg. the ATT/GATT server).
+    type ChannelMapper: ChannelMapper;
+}
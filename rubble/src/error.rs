@@ -0,0 +1,24 @@
+//! The crate's shared error type.
+
+/// Errors that can be returned by Rubble's public APIs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Ran out of buffer space while encoding or decoding a PDU.
+    Eof,
+
+    /// A value was malformed, or did not fit into the field it was being encoded into.
+    InvalidValue,
+
+    /// The operation is not valid in the current state.
+    ///
+    /// For example, returned when a second ATT request is made while one is already outstanding,
+    /// or when an LLCP procedure is started while another is still in progress.
+    InvalidState,
+
+    /// No space is left in a table that has a fixed, static capacity (eg. the open channel
+    /// table).
+    NoFreeSlots,
+
+    /// The connection this operation applies to has been disconnected.
+    Disconnected,
+}
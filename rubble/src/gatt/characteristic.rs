@@ -0,0 +1,76 @@
+use crate::att::AttUuid;
+
+/// Flags describing the operations a client may perform on a characteristic's value.
+///
+/// These correspond to the *Characteristic Properties* bit field defined by the GATT
+/// specification, encoded as a single byte in the characteristic declaration.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Properties(u8);
+
+impl Properties {
+    pub const BROADCAST: Properties = Properties(0x01);
+    pub const READ: Properties = Properties(0x02);
+    pub const WRITE_WITHOUT_RESPONSE: Properties = Properties(0x04);
+    pub const WRITE: Properties = Properties(0x08);
+    pub const NOTIFY: Properties = Properties(0x10);
+    pub const INDICATE: Properties = Properties(0x20);
+
+    /// Returns the raw properties byte as encoded in a characteristic declaration.
+    pub fn as_u8(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns `true` if notifications or indications are enabled, which requires a Client
+    /// Characteristic Configuration Descriptor to be generated alongside the value attribute.
+    pub fn needs_cccd(&self) -> bool {
+        self.0 & (Self::NOTIFY.0 | Self::INDICATE.0) != 0
+    }
+}
+
+impl core::ops::BitOr for Properties {
+    type Output = Properties;
+
+    fn bitor(self, rhs: Properties) -> Properties {
+        Properties(self.0 | rhs.0)
+    }
+}
+
+/// A single characteristic exposed by a [`ServiceSpec`](super::service::ServiceSpec).
+#[derive(Copy, Clone, Debug)]
+pub struct Characteristic {
+    pub uuid: AttUuid,
+    pub properties: Properties,
+}
+
+impl Characteristic {
+    pub const fn new(uuid: AttUuid, properties: Properties) -> Self {
+        Self { uuid, properties }
+    }
+}
+
+/// The external appearance of a device, as advertised by the GAP service.
+///
+/// This mirrors a subset of the Bluetooth SIG's *Appearance Values* assigned numbers.
+#[derive(Copy, Clone, Debug)]
+pub enum Appearance {
+    Unknown,
+    GenericComputer,
+    GenericWatch,
+    GenericTag,
+    GenericSensor,
+    /// An appearance value not covered by the variants above, given as the raw assigned number.
+    Custom(u16),
+}
+
+impl Appearance {
+    pub fn as_u16(&self) -> u16 {
+        match self {
+            Appearance::Unknown => 0x0000,
+            Appearance::GenericComputer => 0x0080,
+            Appearance::GenericWatch => 0x00c0,
+            Appearance::GenericTag => 0x0200,
+            Appearance::GenericSensor => 0x0540,
+            Appearance::Custom(raw) => *raw,
+        }
+    }
+}
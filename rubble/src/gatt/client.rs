@@ -0,0 +1,555 @@
+//! GATT client role: primary-service and characteristic discovery, and value access.
+//!
+//! Unlike the GATT server (whose attribute table is generated at build time by `rubble-codegen`
+//! and served by application code), the client role is small enough that `rubble` implements the
+//! request/response bookkeeping directly. An application holds one [`GattClient`] per connection,
+//! alongside its [`Connection`](crate::link::Connection) and
+//! [`Responder`](crate::link::responder::Responder), and:
+//!
+//! - issues a discovery/read/write request via [`GattClient::tx`], borrowing the connection's
+//!   [`L2CAPStateTx`] to actually send it (so the request respects the negotiated `ATT_MTU`);
+//! - feeds the matching response PDU (delivered to the application's `ChannelMapper`, then handed
+//!   here) into [`GattClient::process_response`] to get the result back.
+//!
+//! Only one request may be outstanding at a time; starting another while one is in flight returns
+//! `Error::InvalidState`.
+//!
+//! Discovery responses may not fit a whole service/characteristic range in one PDU. As with any
+//! GATT client, exhausting a range means re-issuing the request with `start_handle` set to one
+//! past the last handle returned, until a response comes back empty.
+
+use core::marker::PhantomData;
+
+use crate::{
+    att::{AttUuid, OPCODE_ERROR_RESPONSE},
+    bytes::{ByteReader, ByteWriter},
+    config::Config,
+    l2cap::L2CAPStateTx,
+    link::queue::Producer,
+    Error,
+};
+
+/// `ATT_FIND_BY_TYPE_VALUE_REQ`/`RSP` opcodes.
+const OPCODE_FIND_BY_TYPE_VALUE_REQUEST: u8 = 0x06;
+const OPCODE_FIND_BY_TYPE_VALUE_RESPONSE: u8 = 0x07;
+/// `ATT_READ_BY_TYPE_REQ`/`RSP` opcodes.
+const OPCODE_READ_BY_TYPE_REQUEST: u8 = 0x08;
+const OPCODE_READ_BY_TYPE_RESPONSE: u8 = 0x09;
+/// `ATT_READ_REQ`/`RSP` opcodes.
+const OPCODE_READ_REQUEST: u8 = 0x0a;
+const OPCODE_READ_RESPONSE: u8 = 0x0b;
+/// `ATT_READ_BY_GROUP_TYPE_REQ`/`RSP` opcodes.
+const OPCODE_READ_BY_GROUP_TYPE_REQUEST: u8 = 0x10;
+const OPCODE_READ_BY_GROUP_TYPE_RESPONSE: u8 = 0x11;
+/// `ATT_WRITE_REQ`/`RSP` opcodes.
+const OPCODE_WRITE_REQUEST: u8 = 0x12;
+const OPCODE_WRITE_RESPONSE: u8 = 0x13;
+
+/// The `Primary Service` declaration UUID (`0x2800`), used as the group type in
+/// `Read By Group Type Request` to discover primary services.
+const PRIMARY_SERVICE_UUID: u16 = 0x2800;
+/// The `Characteristic` declaration UUID (`0x2803`), used as the attribute type in
+/// `Read By Type Request` to discover characteristics.
+const CHARACTERISTIC_UUID: u16 = 0x2803;
+
+/// Maximum number of discovery results (services or characteristics) collected from one response.
+///
+/// Rubble targets memory-constrained devices, so discovery results are gathered into a
+/// fixed-capacity buffer rather than growing on demand; a range that discovers more than this
+/// many entries in one PDU is walked with a follow-up request, same as with any GATT client.
+const MAX_DISCOVERY_RESULTS: usize = 8;
+
+/// Largest attribute value this client will read or write in one request.
+const MAX_VALUE_LEN: usize = 247;
+
+/// An inclusive range of attribute handles, as used by the discovery requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandleRange {
+    pub start_handle: u16,
+    pub end_handle: u16,
+}
+
+impl HandleRange {
+    /// The full handle space, from the first valid handle to the last.
+    pub fn full() -> Self {
+        Self {
+            start_handle: 0x0001,
+            end_handle: 0xffff,
+        }
+    }
+}
+
+/// One primary service found via [`GattClientTx::discover_primary_services`] or
+/// [`discover_primary_service_by_uuid`](GattClientTx::discover_primary_service_by_uuid).
+#[derive(Debug, Clone, Copy)]
+pub struct DiscoveredService {
+    pub handle_range: HandleRange,
+    /// The service's UUID.
+    ///
+    /// Absent when discovered via `Find By Type Value Request`, since the client already supplied
+    /// the UUID it was searching for and the response doesn't repeat it.
+    pub uuid: Option<AttUuid>,
+}
+
+/// One characteristic found via [`GattClientTx::discover_characteristics`].
+#[derive(Debug, Clone, Copy)]
+pub struct DiscoveredCharacteristic {
+    pub declaration_handle: u16,
+    pub properties: u8,
+    pub value_handle: u16,
+    pub uuid: AttUuid,
+}
+
+/// A fixed-capacity list of [`DiscoveredService`]s, as returned in a [`GattClientEvent::Services`].
+#[derive(Debug, Clone, Copy)]
+pub struct DiscoveredServices {
+    entries: [Option<DiscoveredService>; MAX_DISCOVERY_RESULTS],
+}
+
+impl DiscoveredServices {
+    fn empty() -> Self {
+        Self {
+            entries: [None; MAX_DISCOVERY_RESULTS],
+        }
+    }
+
+    /// Iterates over the services found in the response, in ascending handle order.
+    pub fn iter(&self) -> impl Iterator<Item = &DiscoveredService> {
+        self.entries.iter().filter_map(Option::as_ref)
+    }
+}
+
+/// A fixed-capacity list of [`DiscoveredCharacteristic`]s, as returned in a
+/// [`GattClientEvent::Characteristics`].
+#[derive(Debug, Clone, Copy)]
+pub struct DiscoveredCharacteristics {
+    entries: [Option<DiscoveredCharacteristic>; MAX_DISCOVERY_RESULTS],
+}
+
+impl DiscoveredCharacteristics {
+    fn empty() -> Self {
+        Self {
+            entries: [None; MAX_DISCOVERY_RESULTS],
+        }
+    }
+
+    /// Iterates over the characteristics found in the response, in ascending handle order.
+    pub fn iter(&self) -> impl Iterator<Item = &DiscoveredCharacteristic> {
+        self.entries.iter().filter_map(Option::as_ref)
+    }
+}
+
+/// An attribute value read back via [`GattClientTx::read`].
+#[derive(Debug, Clone, Copy)]
+pub struct AttributeValue {
+    buf: [u8; MAX_VALUE_LEN],
+    len: usize,
+}
+
+impl AttributeValue {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// The result of a request issued through a [`GattClientTx`], obtained from
+/// [`GattClient::process_response`].
+#[derive(Debug, Clone, Copy)]
+pub enum GattClientEvent {
+    /// A `Read By Group Type Response`/`Find By Type Value Response` answering a primary service
+    /// discovery request.
+    Services(DiscoveredServices),
+    /// A `Read By Type Response` answering a characteristic discovery request.
+    Characteristics(DiscoveredCharacteristics),
+    /// A `Read Response` answering [`GattClientTx::read`].
+    Value(AttributeValue),
+    /// A `Write Response` answering [`GattClientTx::write`].
+    WriteComplete,
+    /// An `Error Response`, naming the request it answers and why it failed.
+    Error {
+        request_opcode: u8,
+        attribute_handle: u16,
+        error_code: u8,
+    },
+}
+
+/// The kind of request a [`GattClient`] is currently waiting on a response for.
+///
+/// Tracked so [`GattClient::process_response`] knows how to interpret the reply, without the
+/// caller having to repeat itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PendingRequest {
+    DiscoverPrimaryServices,
+    DiscoverPrimaryServiceByUuid,
+    DiscoverCharacteristics,
+    Read,
+    Write,
+}
+
+/// GATT client state for one connection: which request (if any) is currently outstanding.
+pub struct GattClient<C: Config> {
+    pending: Option<PendingRequest>,
+    _config: PhantomData<C>,
+}
+
+impl<C: Config> GattClient<C> {
+    pub fn new() -> Self {
+        Self {
+            pending: None,
+            _config: PhantomData,
+        }
+    }
+
+    /// Returns `true` if a request is currently outstanding.
+    pub fn request_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Borrows `self` together with the connection's ATT bearer, to issue one request.
+    pub fn tx<'a, P: Producer>(
+        &'a mut self,
+        l2cap: L2CAPStateTx<'a, C::ChannelMapper, P>,
+    ) -> GattClientTx<'a, C, P> {
+        GattClientTx { client: self, l2cap }
+    }
+
+    /// Parses a response PDU for the request currently outstanding on this client.
+    ///
+    /// The application is responsible for recognizing, in its `ChannelMapper`, that an incoming
+    /// ATT PDU is a response to a client request (rather than e.g. a notification) and routing it
+    /// here; `rubble`'s L2CAP layer doesn't do this itself, since it has no way to know which
+    /// connections are acting as a GATT client.
+    ///
+    /// Returns `Error::InvalidState` if no request is outstanding, `Error::Eof` if `payload` is
+    /// empty or ends before a field it must contain, and `Error::InvalidValue` if it otherwise
+    /// doesn't parse as a response to the kind of request that is.
+    pub fn process_response(&mut self, payload: &[u8]) -> Result<GattClientEvent, Error> {
+        let pending = self.pending.take().ok_or(Error::InvalidState)?;
+        let mut reader = ByteReader::new(payload);
+        let opcode = reader.read_u8()?;
+
+        if opcode == OPCODE_ERROR_RESPONSE {
+            return Ok(GattClientEvent::Error {
+                request_opcode: reader.read_u8()?,
+                attribute_handle: reader.read_u16_le()?,
+                error_code: reader.read_u8()?,
+            });
+        }
+
+        match pending {
+            PendingRequest::DiscoverPrimaryServices => {
+                parse_read_by_group_type_response(opcode, &mut reader)
+            }
+            PendingRequest::DiscoverPrimaryServiceByUuid => {
+                parse_find_by_type_value_response(opcode, &mut reader)
+            }
+            PendingRequest::DiscoverCharacteristics => {
+                parse_read_by_type_response(opcode, &mut reader)
+            }
+            PendingRequest::Read => parse_read_response(opcode, &mut reader),
+            PendingRequest::Write => parse_write_response(opcode),
+        }
+    }
+}
+
+fn parse_read_by_group_type_response(
+    opcode: u8,
+    reader: &mut ByteReader<'_>,
+) -> Result<GattClientEvent, Error> {
+    if opcode != OPCODE_READ_BY_GROUP_TYPE_RESPONSE {
+        return Err(Error::InvalidValue);
+    }
+    let entry_len = reader.read_u8()? as usize;
+    if entry_len < 4 {
+        return Err(Error::InvalidValue);
+    }
+    let uuid_len = entry_len - 4;
+
+    let mut services = DiscoveredServices::empty();
+    let mut count = 0;
+    while reader.bytes_left() >= entry_len {
+        let start_handle = reader.read_u16_le()?;
+        let end_handle = reader.read_u16_le()?;
+        let uuid = read_uuid(reader.read_slice(uuid_len)?)?;
+        if count < MAX_DISCOVERY_RESULTS {
+            services.entries[count] = Some(DiscoveredService {
+                handle_range: HandleRange { start_handle, end_handle },
+                uuid: Some(uuid),
+            });
+            count += 1;
+        }
+    }
+    Ok(GattClientEvent::Services(services))
+}
+
+fn parse_find_by_type_value_response(
+    opcode: u8,
+    reader: &mut ByteReader<'_>,
+) -> Result<GattClientEvent, Error> {
+    if opcode != OPCODE_FIND_BY_TYPE_VALUE_RESPONSE {
+        return Err(Error::InvalidValue);
+    }
+    let mut services = DiscoveredServices::empty();
+    let mut count = 0;
+    while reader.bytes_left() >= 4 {
+        let start_handle = reader.read_u16_le()?;
+        let end_handle = reader.read_u16_le()?;
+        if count < MAX_DISCOVERY_RESULTS {
+            services.entries[count] = Some(DiscoveredService {
+                handle_range: HandleRange { start_handle, end_handle },
+                uuid: None,
+            });
+            count += 1;
+        }
+    }
+    Ok(GattClientEvent::Services(services))
+}
+
+fn parse_read_by_type_response(
+    opcode: u8,
+    reader: &mut ByteReader<'_>,
+) -> Result<GattClientEvent, Error> {
+    if opcode != OPCODE_READ_BY_TYPE_RESPONSE {
+        return Err(Error::InvalidValue);
+    }
+    let entry_len = reader.read_u8()? as usize;
+    // Characteristic declaration value: properties(1) + value_handle(2) + uuid(2 or 16).
+    if entry_len < 2 + 1 + 2 {
+        return Err(Error::InvalidValue);
+    }
+    let uuid_len = entry_len - (2 + 1 + 2);
+
+    let mut characteristics = DiscoveredCharacteristics::empty();
+    let mut count = 0;
+    while reader.bytes_left() >= entry_len {
+        let declaration_handle = reader.read_u16_le()?;
+        let properties = reader.read_u8()?;
+        let value_handle = reader.read_u16_le()?;
+        let uuid = read_uuid(reader.read_slice(uuid_len)?)?;
+        if count < MAX_DISCOVERY_RESULTS {
+            characteristics.entries[count] = Some(DiscoveredCharacteristic {
+                declaration_handle,
+                properties,
+                value_handle,
+                uuid,
+            });
+            count += 1;
+        }
+    }
+    Ok(GattClientEvent::Characteristics(characteristics))
+}
+
+fn parse_read_response(opcode: u8, reader: &mut ByteReader<'_>) -> Result<GattClientEvent, Error> {
+    if opcode != OPCODE_READ_RESPONSE {
+        return Err(Error::InvalidValue);
+    }
+    let value = reader.rest();
+    if value.len() > MAX_VALUE_LEN {
+        return Err(Error::InvalidValue);
+    }
+    let mut buf = [0u8; MAX_VALUE_LEN];
+    buf[..value.len()].copy_from_slice(value);
+    Ok(GattClientEvent::Value(AttributeValue {
+        buf,
+        len: value.len(),
+    }))
+}
+
+fn parse_write_response(opcode: u8) -> Result<GattClientEvent, Error> {
+    if opcode != OPCODE_WRITE_RESPONSE {
+        return Err(Error::InvalidValue);
+    }
+    Ok(GattClientEvent::WriteComplete)
+}
+
+/// Parses a UUID in the 2- or 16-byte wire encoding ATT uses inline in PDUs.
+fn read_uuid(bytes: &[u8]) -> Result<AttUuid, Error> {
+    match bytes.len() {
+        2 => Ok(AttUuid::Uuid16(u16::from_le_bytes([bytes[0], bytes[1]]))),
+        16 => {
+            let mut raw = [0u8; 16];
+            raw.copy_from_slice(bytes);
+            Ok(AttUuid::Uuid128(raw))
+        }
+        _ => Err(Error::InvalidValue),
+    }
+}
+
+/// Temporary handle combining a [`GattClient`] with the connection's ATT bearer, used to issue one
+/// request.
+pub struct GattClientTx<'a, C: Config, P: Producer> {
+    client: &'a mut GattClient<C>,
+    l2cap: L2CAPStateTx<'a, C::ChannelMapper, P>,
+}
+
+impl<'a, C: Config, P: Producer> GattClientTx<'a, C, P> {
+    fn request(
+        mut self,
+        pending: PendingRequest,
+        size: u8,
+        f: impl FnOnce(&mut ByteWriter<'_>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if self.client.request_pending() {
+            return Err(Error::InvalidState);
+        }
+
+        let mut buf = [0u8; MAX_VALUE_LEN];
+        let payload_len = {
+            let mut writer = ByteWriter::new(&mut buf[..size as usize]);
+            f(&mut writer)?;
+            writer.len()
+        };
+        self.l2cap.send_att_pdu(&buf[..payload_len])?;
+        self.client.pending = Some(pending);
+        Ok(())
+    }
+
+    /// Discovers all primary services in `range`, via `Read By Group Type Request`.
+    pub fn discover_primary_services(self, range: HandleRange) -> Result<(), Error> {
+        self.request(PendingRequest::DiscoverPrimaryServices, 7, |writer| {
+            writer.write_u8(OPCODE_READ_BY_GROUP_TYPE_REQUEST)?;
+            writer.write_u16_le(range.start_handle)?;
+            writer.write_u16_le(range.end_handle)?;
+            writer.write_u16_le(PRIMARY_SERVICE_UUID)
+        })
+    }
+
+    /// Discovers primary services matching `uuid` in `range`, via `Find By Type Value Request`.
+    pub fn discover_primary_service_by_uuid(
+        self,
+        range: HandleRange,
+        uuid: AttUuid,
+    ) -> Result<(), Error> {
+        let uuid16 = match uuid {
+            AttUuid::Uuid16(uuid) => uuid,
+            // Only 16-bit UUIDs are supported here: the request is built with a 2-byte attribute
+            // value, so 32- and 128-bit UUIDs are rejected.
+            _ => return Err(Error::InvalidValue),
+        };
+        self.request(PendingRequest::DiscoverPrimaryServiceByUuid, 9, |writer| {
+            writer.write_u8(OPCODE_FIND_BY_TYPE_VALUE_REQUEST)?;
+            writer.write_u16_le(range.start_handle)?;
+            writer.write_u16_le(range.end_handle)?;
+            writer.write_u16_le(PRIMARY_SERVICE_UUID)?;
+            writer.write_u16_le(uuid16)
+        })
+    }
+
+    /// Discovers all characteristics of a service spanning `range`, via `Read By Type Request`.
+    pub fn discover_characteristics(self, range: HandleRange) -> Result<(), Error> {
+        self.request(PendingRequest::DiscoverCharacteristics, 7, |writer| {
+            writer.write_u8(OPCODE_READ_BY_TYPE_REQUEST)?;
+            writer.write_u16_le(range.start_handle)?;
+            writer.write_u16_le(range.end_handle)?;
+            writer.write_u16_le(CHARACTERISTIC_UUID)
+        })
+    }
+
+    /// Reads the value of the attribute at `handle`, via `Read Request`.
+    pub fn read(self, handle: u16) -> Result<(), Error> {
+        self.request(PendingRequest::Read, 3, |writer| {
+            writer.write_u8(OPCODE_READ_REQUEST)?;
+            writer.write_u16_le(handle)
+        })
+    }
+
+    /// Writes `value` to the attribute at `handle`, via `Write Request`.
+    pub fn write(self, handle: u16, value: &[u8]) -> Result<(), Error> {
+        if value.len() > MAX_VALUE_LEN - 3 {
+            return Err(Error::InvalidValue);
+        }
+        let size = 3 + value.len() as u8;
+        self.request(PendingRequest::Write, size, |writer| {
+            writer.write_u8(OPCODE_WRITE_REQUEST)?;
+            writer.write_u16_le(handle)?;
+            writer.write_slice(value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_by_group_type_response_collects_services() {
+        // entry_len = 6 (start + end + 16-bit UUID), two entries.
+        let payload = [
+            OPCODE_READ_BY_GROUP_TYPE_RESPONSE,
+            6,
+            0x01, 0x00, 0x05, 0x00, 0x00, 0x18, // handles 1..=5, UUID 0x1800
+            0x06, 0x00, 0x0a, 0x00, 0x01, 0x18, // handles 6..=10, UUID 0x1801
+        ];
+        let mut reader = ByteReader::new(&payload[1..]);
+        let event = parse_read_by_group_type_response(payload[0], &mut reader).unwrap();
+        let services = match event {
+            GattClientEvent::Services(services) => services,
+            _ => panic!("expected Services"),
+        };
+        let mut iter = services.iter();
+        let first = *iter.next().unwrap();
+        let second = *iter.next().unwrap();
+        assert!(iter.next().is_none());
+        assert_eq!(first.handle_range, HandleRange { start_handle: 1, end_handle: 5 });
+        assert_eq!(first.uuid, Some(AttUuid::Uuid16(0x1800)));
+        assert_eq!(second.handle_range, HandleRange { start_handle: 6, end_handle: 10 });
+    }
+
+    #[test]
+    fn find_by_type_value_response_collects_services_without_uuid() {
+        let payload = [
+            OPCODE_FIND_BY_TYPE_VALUE_RESPONSE,
+            0x01, 0x00, 0x05, 0x00, // handles 1..=5
+        ];
+        let mut reader = ByteReader::new(&payload[1..]);
+        let event = parse_find_by_type_value_response(payload[0], &mut reader).unwrap();
+        let services = match event {
+            GattClientEvent::Services(services) => services,
+            _ => panic!("expected Services"),
+        };
+        let mut iter = services.iter();
+        let first = *iter.next().unwrap();
+        assert!(iter.next().is_none());
+        assert_eq!(first.handle_range, HandleRange { start_handle: 1, end_handle: 5 });
+        assert_eq!(first.uuid, None);
+    }
+
+    #[test]
+    fn read_by_type_response_collects_characteristics() {
+        // entry_len = 7 (decl_handle + properties + value_handle + 16-bit UUID).
+        let payload = [
+            OPCODE_READ_BY_TYPE_RESPONSE,
+            7,
+            0x02, 0x00, 0x02, 0x03, 0x00, 0x00, 0x2a, // decl=2, props=READ, value=3, UUID 0x2a00
+        ];
+        let mut reader = ByteReader::new(&payload[1..]);
+        let event = parse_read_by_type_response(payload[0], &mut reader).unwrap();
+        let chars = match event {
+            GattClientEvent::Characteristics(chars) => chars,
+            _ => panic!("expected Characteristics"),
+        };
+        let mut iter = chars.iter();
+        let first = *iter.next().unwrap();
+        assert!(iter.next().is_none());
+        assert_eq!(first.declaration_handle, 2);
+        assert_eq!(first.value_handle, 3);
+        assert_eq!(first.uuid, AttUuid::Uuid16(0x2a00));
+    }
+
+    #[test]
+    fn read_response_parses_value() {
+        let payload = [OPCODE_READ_RESPONSE, 1, 2, 3];
+        let mut reader = ByteReader::new(&payload[1..]);
+        let event = parse_read_response(payload[0], &mut reader).unwrap();
+        match event {
+            GattClientEvent::Value(value) => assert_eq!(value.as_bytes(), &[1, 2, 3]),
+            _ => panic!("expected Value"),
+        }
+    }
+
+    #[test]
+    fn write_response_requires_matching_opcode() {
+        assert!(parse_write_response(OPCODE_WRITE_RESPONSE).is_ok());
+        assert!(parse_write_response(OPCODE_READ_RESPONSE).is_err());
+    }
+}
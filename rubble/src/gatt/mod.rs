@@ -0,0 +1,5 @@
+//! Generic Attribute Profile (GATT): services and characteristics built on top of ATT.
+
+pub mod characteristic;
+pub mod client;
+pub mod service;
@@ -24,7 +24,12 @@ pub trait ServiceSpec {
     const SINGLETON: bool;
 
     /// Iterator over characteristic specifications.
-    type Characteristics: Iterator<Item = AttUuid>;
+    type Characteristics: Iterator<Item = Characteristic>;
+
+    /// Returns an iterator over the characteristics making up this service.
+    ///
+    /// Called by `rubble-codegen` while walking the service to assign attribute handles.
+    fn characteristics() -> Self::Characteristics;
 }
 
 /// The type of a service (primary or secondary).
@@ -49,8 +54,31 @@ pub enum AllowedType {
     Any,
 }
 
-pub struct GapService {}
+/// The mandatory GAP (Generic Access Profile) service.
+///
+/// Every BLE device exposes this service, so unlike other services it isn't described via
+/// [`ServiceSpec`] — `rubble-codegen`'s `Builder` always generates it from a device name and
+/// [`Appearance`] passed to `Builder::gap`.
+pub struct GapService<'a> {
+    device_name: &'a str,
+    appearance: Appearance,
+}
+
+impl<'a> GapService<'a> {
+    pub fn new(device_name: &'a str, appearance: Appearance) -> Self {
+        Self {
+            device_name,
+            appearance,
+        }
+    }
+
+    /// The device name to expose via the *Device Name* characteristic.
+    pub fn device_name(&self) -> &'a str {
+        self.device_name
+    }
 
-impl GapService {
-    pub fn new(device_name: &str, appearance: Appearance) {}
+    /// The appearance to expose via the *Appearance* characteristic.
+    pub fn appearance(&self) -> Appearance {
+        self.appearance
+    }
 }
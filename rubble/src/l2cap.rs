@@ -0,0 +1,1067 @@
+//! Logical Link Control and Adaptation Protocol (L2CAP).
+//!
+//! This module reassembles Data Channel PDUs into full L2CAP packets (see
+//! [`L2CAPStateTx::process_start`]/[`process_cont`](L2CAPStateTx::process_cont)), routes fixed
+//! channels (ATT, LE Signaling), negotiates `ATT_MTU` via `Exchange MTU` on the unenhanced ATT
+//! bearer, and manages dynamically negotiated credit-based channels:
+//!
+//! - *LE Credit-Based Connection-Oriented Channels* (CoC), which let the application stream bulk
+//!   data alongside the ATT bearer, negotiated one channel at a time via
+//!   `LE_CREDIT_BASED_CONNECTION_REQ`/`RSP`.
+//! - *Enhanced Credit-Based* channels (ECRED), which negotiate a batch of channels in one
+//!   request/response and are how multiple concurrent ATT bearers (Enhanced ATT, EATT) are set up.
+
+use crate::{
+    att::{self, AttPdu},
+    bytes::{ByteReader, ByteWriter, FromBytes, ToBytes},
+    link::{data::Llid, queue::{Consume, Producer}},
+    Error,
+};
+
+/// CID of the fixed Attribute Protocol channel.
+pub const ATT_CID: u16 = 0x0004;
+/// CID of the fixed LE Signaling channel.
+pub const SIGNALING_CID: u16 = 0x0005;
+
+/// First CID available for dynamic (application-negotiated) channels.
+const DYNAMIC_CID_START: u16 = 0x0040;
+
+/// Maximum number of concurrently open CoC channels.
+///
+/// Rubble targets memory-constrained devices, so this table has a fixed, static capacity rather
+/// than growing on demand.
+const MAX_CHANNELS: usize = 4;
+
+/// Largest L2CAP basic frame (header + payload) this implementation will reassemble.
+const MAX_FRAME_SIZE: usize = 512;
+
+/// Largest SDU this implementation will reassemble on a CoC channel.
+const MAX_SDU_SIZE: usize = 512;
+
+/// Smallest MTU/MPS a peer may advertise when opening or accepting a credit-based channel (CoC or
+/// ECRED), per the Core Spec's `LE_CREDIT_BASED_CONNECTION_REQ`/`L2CAP_ECRED_CONN_REQ` parameter
+/// ranges.
+///
+/// [`kframe_count`] and [`L2CAPStateTx::send_sdu`] subtract 2 (the SDU-length prefix) from a
+/// channel's MPS without a further check, so admitting a channel with a smaller MPS would panic
+/// the first time we send on it.
+const MIN_LE_CREDIT_MTU_MPS: u16 = 23;
+
+/// Identifies which ATT bearer a PDU was exchanged over.
+///
+/// Normally there is only the one, always-present bearer, but Enhanced ATT (EATT) allows
+/// additional bearers to be opened as Enhanced Credit-Based channels so multiple ATT transactions
+/// can be in flight concurrently.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AttBearer {
+    /// The classic ATT bearer carried on the fixed ATT channel (CID `0x0004`).
+    Unenhanced,
+    /// An Enhanced ATT bearer opened via `L2CAP_ECRED_CONN_REQ`, identified by its local CID.
+    Enhanced(u16),
+}
+
+/// Maps incoming L2CAP traffic that isn't handled by this module directly to application state.
+///
+/// At minimum this gives the L2CAP layer somewhere to deliver ATT PDUs, whether they arrive on the
+/// fixed ATT channel or on one of potentially several Enhanced ATT bearers; `rubble`'s GATT
+/// server/client build on top of it.
+pub trait ChannelMapper {
+    /// Called with the payload of an ATT PDU received on `bearer`.
+    fn on_att_pdu(&mut self, bearer: AttBearer, payload: &[u8]);
+}
+
+/// A handle to an open LE Credit-Based Connection-Oriented Channel.
+///
+/// Obtained from [`L2CAPStateTx::open_channel`], for channels we initiate. Channels accepted from
+/// an incoming `LE_CREDIT_BASED_CONNECTION_REQ` are not handed to the application as a handle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChannelHandle(u16);
+
+struct CocChannel {
+    /// Our end of the channel; CIDs on this side are always in `DYNAMIC_CID_START..`.
+    local_cid: u16,
+    /// The peer's CID for this channel, used as the destination when we send K-frames.
+    peer_cid: u16,
+    /// MTU the peer advertised: the largest SDU it accepts from us.
+    peer_mtu: u16,
+    /// MPS (maximum payload size per K-frame) the peer advertised.
+    peer_mps: u16,
+    /// Credits we currently hold to send K-frames to the peer.
+    peer_credits: u16,
+    /// MTU we advertised for SDUs we receive.
+    local_mtu: u16,
+    /// MPS we advertised for K-frames we receive.
+    local_mps: u16,
+    /// Whether this channel carries an Enhanced ATT bearer, in which case completed SDUs are
+    /// delivered straight to the [`ChannelMapper`] instead of being queued for the application.
+    eatt: bool,
+    /// The signaling identifier of our own outstanding `LE_CREDIT_BASED_CONNECTION_REQ`, while we
+    /// are still waiting for its `_RSP`.
+    ///
+    /// Lets [`L2CAPStateTx::handle_credit_based_connection_rsp`] correlate an incoming response
+    /// with the channel that requested it even if another `open_channel` call is outstanding at
+    /// the same time, rather than guessing from `peer_cid == 0` alone.
+    pending_identifier: Option<u8>,
+    rx: SduReassembly,
+}
+
+/// Reassembles K-frames (possibly several) back into a full SDU.
+struct SduReassembly {
+    buf: [u8; MAX_SDU_SIZE],
+    expected_len: u16,
+    have: u16,
+    active: bool,
+    complete: bool,
+}
+
+impl SduReassembly {
+    fn new() -> Self {
+        Self {
+            buf: [0; MAX_SDU_SIZE],
+            expected_len: 0,
+            have: 0,
+            active: false,
+            complete: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.expected_len = 0;
+        self.have = 0;
+        self.active = false;
+        self.complete = false;
+    }
+
+    /// Feeds one received K-frame's payload into the reassembly buffer.
+    ///
+    /// `local_mtu` bounds the SDU length the peer is allowed to send us.
+    fn feed(&mut self, kframe: &[u8], local_mtu: u16) -> Result<(), Error> {
+        let payload = if !self.active {
+            // The first K-frame of an SDU is prefixed with the 2-byte SDU length.
+            let mut reader = ByteReader::new(kframe);
+            let sdu_len = reader.read_u16_le()?;
+            if sdu_len > local_mtu {
+                return Err(Error::InvalidValue);
+            }
+            self.expected_len = sdu_len;
+            self.have = 0;
+            self.active = true;
+            self.complete = false;
+            reader.rest()
+        } else {
+            kframe
+        };
+
+        if self.have as usize + payload.len() > self.buf.len()
+            || self.have + payload.len() as u16 > self.expected_len
+        {
+            self.reset();
+            return Err(Error::InvalidValue);
+        }
+
+        let start = self.have as usize;
+        self.buf[start..start + payload.len()].copy_from_slice(payload);
+        self.have += payload.len() as u16;
+
+        if self.have == self.expected_len {
+            self.active = false;
+            self.complete = true;
+        }
+        Ok(())
+    }
+
+    fn take(&mut self) -> Option<&[u8]> {
+        if self.complete {
+            self.complete = false;
+            let len = self.have as usize;
+            self.have = 0;
+            Some(&self.buf[..len])
+        } else {
+            None
+        }
+    }
+}
+
+/// Reassembles Data Channel PDU fragments (`DataStart`/`DataCont`) into full L2CAP basic frames.
+struct FrameReassembly {
+    buf: [u8; MAX_FRAME_SIZE],
+    cid: u16,
+    expected_len: u16,
+    have: u16,
+    active: bool,
+}
+
+impl FrameReassembly {
+    fn new() -> Self {
+        Self {
+            buf: [0; MAX_FRAME_SIZE],
+            cid: 0,
+            expected_len: 0,
+            have: 0,
+            active: false,
+        }
+    }
+}
+
+/// Per-connection L2CAP state: fixed-channel routing, the signaling channel, and the CoC channel
+/// table.
+pub struct L2CAPState<M: ChannelMapper> {
+    mapper: M,
+    frame: FrameReassembly,
+    channels: [Option<CocChannel>; MAX_CHANNELS],
+    next_local_cid: u16,
+    /// Signaling identifier to use for the next self-initiated request (`LE_CREDIT_BASED_CONNECTION_REQ`
+    /// and friends), cycled through `0x01..=0xff` since `0x00` is reserved.
+    next_identifier: u8,
+    /// The negotiated `ATT_MTU` for the unenhanced ATT bearer.
+    att_mtu: u16,
+    /// Whether `Exchange MTU` has already been performed on the unenhanced ATT bearer.
+    ///
+    /// The procedure may only run once per bearer; Enhanced ATT bearers don't have this
+    /// restriction, since they negotiate their MTU as part of opening the channel instead.
+    att_mtu_exchanged: bool,
+}
+
+impl<M: ChannelMapper> L2CAPState<M> {
+    pub fn new(mapper: M) -> Self {
+        Self {
+            mapper,
+            frame: FrameReassembly::new(),
+            channels: [None, None, None, None],
+            next_local_cid: DYNAMIC_CID_START,
+            next_identifier: 1,
+            att_mtu: att::ATT_DEFAULT_MTU,
+            att_mtu_exchanged: false,
+        }
+    }
+
+    /// The `ATT_MTU` currently in effect on the unenhanced ATT bearer.
+    ///
+    /// This is [`att::ATT_DEFAULT_MTU`] until `Exchange MTU` completes, after which it is the
+    /// smaller of our own and the peer's proposed receive MTU.
+    pub fn att_mtu(&self) -> u16 {
+        self.att_mtu
+    }
+
+    /// Borrows `self` together with an outgoing packet producer, for one operation.
+    pub fn tx<'a, P: Producer>(&'a mut self, producer: &'a mut P) -> L2CAPStateTx<'a, M, P> {
+        L2CAPStateTx {
+            state: self,
+            producer,
+        }
+    }
+
+    /// Clears all dynamic channel and reassembly state.
+    ///
+    /// Must be called when the underlying Link Layer connection is torn down, since CIDs and
+    /// credit counts are only meaningful for the connection they were negotiated on.
+    pub fn reset(&mut self) {
+        self.frame = FrameReassembly::new();
+        self.channels = [None, None, None, None];
+        self.next_local_cid = DYNAMIC_CID_START;
+        self.next_identifier = 1;
+        self.att_mtu = att::ATT_DEFAULT_MTU;
+        self.att_mtu_exchanged = false;
+    }
+
+    fn alloc_local_cid(&mut self) -> Option<u16> {
+        let slot = self.channels.iter().position(|c| c.is_none())?;
+        let cid = self.next_local_cid;
+        self.next_local_cid += 1;
+        let _ = slot;
+        Some(cid)
+    }
+
+    /// Allocates the signaling identifier for our next self-initiated request, cycling through
+    /// `0x01..=0xff`.
+    fn alloc_identifier(&mut self) -> u8 {
+        let id = self.next_identifier;
+        self.next_identifier = if self.next_identifier == 0xff {
+            1
+        } else {
+            self.next_identifier + 1
+        };
+        id
+    }
+
+    fn channel_mut(&mut self, local_cid: u16) -> Option<&mut CocChannel> {
+        self.channels
+            .iter_mut()
+            .flatten()
+            .find(|c| c.local_cid == local_cid)
+    }
+
+    fn free_slot(&mut self) -> Option<&mut Option<CocChannel>> {
+        self.channels.iter_mut().find(|c| c.is_none())
+    }
+}
+
+/// Temporary handle combining an [`L2CAPState`] with the outgoing packet queue, used to process
+/// one incoming PDU or originate one request.
+pub struct L2CAPStateTx<'a, M: ChannelMapper, P: Producer> {
+    state: &'a mut L2CAPState<M>,
+    producer: &'a mut P,
+}
+
+impl<'a, M: ChannelMapper, P: Producer> L2CAPStateTx<'a, M, P> {
+    /// Processes the first (or only) fragment of an L2CAP basic frame.
+    pub fn process_start(&mut self, message: &[u8]) -> Consume<Result<(), Error>> {
+        Consume::always(self.process_start_inner(message))
+    }
+
+    fn process_start_inner(&mut self, message: &[u8]) -> Result<(), Error> {
+        let mut reader = ByteReader::new(message);
+        let length = reader.read_u16_le()?;
+        let cid = reader.read_u16_le()?;
+        let payload = reader.rest();
+
+        if payload.len() as u16 >= length {
+            // The whole frame arrived in this one fragment.
+            let frame = &payload[..length as usize];
+            return self.dispatch(cid, frame);
+        }
+
+        if (length as usize) > self.state.frame.buf.len() {
+            return Err(Error::InvalidValue);
+        }
+        self.state.frame.cid = cid;
+        self.state.frame.expected_len = length;
+        self.state.frame.have = payload.len() as u16;
+        self.state.frame.buf[..payload.len()].copy_from_slice(payload);
+        self.state.frame.active = true;
+        Ok(())
+    }
+
+    /// Processes a continuation fragment of an L2CAP basic frame.
+    pub fn process_cont(&mut self, message: &[u8]) -> Consume<Result<(), Error>> {
+        Consume::always(self.process_cont_inner(message))
+    }
+
+    fn process_cont_inner(&mut self, message: &[u8]) -> Result<(), Error> {
+        if !self.state.frame.active {
+            return Err(Error::InvalidState);
+        }
+        let have = self.state.frame.have as usize;
+        if have + message.len() > self.state.frame.buf.len()
+            || self.state.frame.have + message.len() as u16 > self.state.frame.expected_len
+        {
+            self.state.frame.active = false;
+            return Err(Error::InvalidValue);
+        }
+        self.state.frame.buf[have..have + message.len()].copy_from_slice(message);
+        self.state.frame.have += message.len() as u16;
+
+        if self.state.frame.have == self.state.frame.expected_len {
+            self.state.frame.active = false;
+            let cid = self.state.frame.cid;
+            let len = self.state.frame.have as usize;
+            let mut frame = [0u8; MAX_FRAME_SIZE];
+            frame[..len].copy_from_slice(&self.state.frame.buf[..len]);
+            return self.dispatch(cid, &frame[..len]);
+        }
+        Ok(())
+    }
+
+    fn dispatch(&mut self, cid: u16, frame: &[u8]) -> Result<(), Error> {
+        match cid {
+            ATT_CID => self.handle_att_pdu(frame),
+            SIGNALING_CID => self.handle_signaling(frame),
+            cid => self.handle_coc_kframe(cid, frame),
+        }
+    }
+
+    /// Handles a PDU received on the unenhanced ATT bearer (CID `0x0004`).
+    ///
+    /// `Exchange MTU` is intercepted here, since negotiating `ATT_MTU` is a property of the
+    /// bearer itself; everything else is opaque to this layer and forwarded straight to the
+    /// [`ChannelMapper`].
+    fn handle_att_pdu(&mut self, frame: &[u8]) -> Result<(), Error> {
+        if frame.len() as u16 > self.state.att_mtu {
+            // A peer that hasn't exchanged MTU yet is bound to `ATT_DEFAULT_MTU`; one that has is
+            // bound to the negotiated value. Either way, a longer PDU is a protocol violation.
+            return Err(Error::InvalidValue);
+        }
+
+        if frame.first() == Some(&att::OPCODE_EXCHANGE_MTU_REQUEST) {
+            return self.handle_exchange_mtu_request(frame);
+        }
+
+        self.state.mapper.on_att_pdu(AttBearer::Unenhanced, frame);
+        Ok(())
+    }
+
+    /// Handles `ATT_EXCHANGE_MTU_REQ`, answering with `ATT_EXCHANGE_MTU_RSP`.
+    ///
+    /// A second attempt on the same bearer is rejected with `ATT_ERROR_RSP`, since the procedure
+    /// may only be performed once.
+    fn handle_exchange_mtu_request(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let client_rx_mtu = match AttPdu::from_bytes(&mut ByteReader::new(frame))? {
+            AttPdu::ExchangeMtuRequest { client_rx_mtu } => client_rx_mtu,
+            _ => unreachable!("frame starts with the Exchange MTU Request opcode"),
+        };
+
+        if self.state.att_mtu_exchanged {
+            return self.send_att_pdu_raw(&AttPdu::ErrorResponse {
+                request_opcode: att::OPCODE_EXCHANGE_MTU_REQUEST,
+                attribute_handle: 0x0000,
+                error_code: att::ERROR_REQUEST_NOT_SUPPORTED,
+            });
+        }
+
+        self.state.att_mtu = client_rx_mtu.min(att::ATT_MAX_MTU);
+        self.state.att_mtu_exchanged = true;
+
+        self.send_att_pdu_raw(&AttPdu::ExchangeMtuResponse {
+            server_rx_mtu: att::ATT_MAX_MTU,
+        })
+    }
+
+    /// Sends `pdu` on the unenhanced ATT bearer, bypassing the `att_mtu` size check in
+    /// [`send_att_pdu`](Self::send_att_pdu) since Exchange MTU responses are exempt from the MTU
+    /// they themselves establish.
+    fn send_att_pdu_raw(&mut self, pdu: &AttPdu) -> Result<(), Error> {
+        let size = pdu.encoded_size();
+        self.producer.produce_with(4 + size, |writer| {
+            writer.write_u16_le(size as u16)?;
+            writer.write_u16_le(ATT_CID)?;
+            pdu.to_bytes(writer)?;
+            Ok(Llid::DataStart)
+        })
+    }
+
+    /// Sends a raw ATT PDU (e.g. a `Handle Value Notification`/`Indication`) on the unenhanced
+    /// ATT bearer.
+    ///
+    /// Rejects payloads larger than the negotiated [`att_mtu`](Self::att_mtu), so outgoing
+    /// notifications/indications always respect what the peer can actually receive.
+    pub fn send_att_pdu(&mut self, payload: &[u8]) -> Result<(), Error> {
+        if payload.len() as u16 > self.state.att_mtu {
+            return Err(Error::InvalidValue);
+        }
+        self.producer.produce_with((4 + payload.len()) as u8, |writer| {
+            writer.write_u16_le(payload.len() as u16)?;
+            writer.write_u16_le(ATT_CID)?;
+            writer.write_slice(payload)?;
+            Ok(Llid::DataStart)
+        })
+    }
+
+    /// Sends a raw ATT PDU on `bearer`.
+    ///
+    /// On [`AttBearer::Unenhanced`] this is exactly [`send_att_pdu`](Self::send_att_pdu). On
+    /// [`AttBearer::Enhanced`] it is sent as an SDU over the backing Enhanced Credit-Based channel
+    /// via [`send_sdu`](Self::send_sdu), so replies to ATT requests received on an EATT bearer
+    /// (opened via `L2CAP_ECRED_CONN_REQ`) have somewhere to go.
+    pub fn send_att_pdu_on(&mut self, bearer: AttBearer, payload: &[u8]) -> Result<(), Error> {
+        match bearer {
+            AttBearer::Unenhanced => self.send_att_pdu(payload),
+            AttBearer::Enhanced(cid) => self.send_sdu(ChannelHandle(cid), payload),
+        }
+    }
+
+    /// The `ATT_MTU` currently in effect on the unenhanced ATT bearer.
+    pub fn att_mtu(&self) -> u16 {
+        self.state.att_mtu()
+    }
+
+    fn handle_coc_kframe(&mut self, local_cid: u16, kframe: &[u8]) -> Result<(), Error> {
+        let channel = self
+            .state
+            .channel_mut(local_cid)
+            .ok_or(Error::InvalidState)?;
+        let local_mtu = channel.local_mtu;
+        let eatt = channel.eatt;
+        channel.rx.feed(kframe, local_mtu)?;
+
+        if eatt {
+            let mut sdu = [0u8; MAX_SDU_SIZE];
+            let len = match self.state.channel_mut(local_cid).unwrap().rx.take() {
+                Some(taken) => {
+                    sdu[..taken.len()].copy_from_slice(taken);
+                    taken.len()
+                }
+                None => return Ok(()),
+            };
+            self.state
+                .mapper
+                .on_att_pdu(AttBearer::Enhanced(local_cid), &sdu[..len]);
+        }
+        Ok(())
+    }
+
+    fn handle_signaling(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let mut reader = ByteReader::new(frame);
+        let code = reader.read_u8()?;
+        let identifier = reader.read_u8()?;
+        let data_length = reader.read_u16_le()?;
+        let data = reader.read_slice(data_length as usize)?;
+
+        match code {
+            0x14 => self.handle_credit_based_connection_req(identifier, data),
+            0x15 => self.handle_credit_based_connection_rsp(identifier, data),
+            0x17 => self.handle_ecred_conn_req(identifier, data),
+            0x19 => self.handle_ecred_reconfigure_req(identifier, data),
+            _ => Ok(()),
+        }
+    }
+
+    /// Handles `L2CAP_ECRED_CONN_REQ`: a single PSM/MTU/MPS/credits negotiation applied to a
+    /// *list* of source CIDs, allocating one destination CID per requested channel.
+    ///
+    /// This MUST always be answered with `L2CAP_ECRED_CONN_RSP` (`0x18`), never the legacy
+    /// `LE_CREDIT_BASED_CONNECTION_RSP` (`0x15`) — responding with the wrong PDU breaks
+    /// interoperability with peers that correctly implement ECRED.
+    fn handle_ecred_conn_req(&mut self, identifier: u8, data: &[u8]) -> Result<(), Error> {
+        let mut reader = ByteReader::new(data);
+        let _le_psm = reader.read_u16_le()?;
+        let mtu = reader.read_u16_le()?;
+        let mps = reader.read_u16_le()?;
+        let initial_credits = reader.read_u16_le()?;
+
+        const OUR_MTU: u16 = 256;
+        const OUR_MPS: u16 = 128;
+        const OUR_INITIAL_CREDITS: u16 = 8;
+        const MAX_REQUESTED_CIDS: usize = MAX_CHANNELS;
+
+        // Count every source CID the peer sent, even beyond what we can store, so a request for
+        // more channels than we support is refused outright rather than silently truncated into a
+        // shorter (non-conformant) destination-CID list.
+        let mut source_cids = [0u16; MAX_REQUESTED_CIDS];
+        let mut num_cids = 0;
+        let mut total_requested = 0usize;
+        while reader.bytes_left() >= 2 {
+            let cid = reader.read_u16_le()?;
+            if total_requested < MAX_REQUESTED_CIDS {
+                source_cids[num_cids] = cid;
+                num_cids += 1;
+            }
+            total_requested += 1;
+        }
+
+        let free_slots = self.state.channels.iter().filter(|c| c.is_none()).count();
+        let mut overall_result = if mtu < MIN_LE_CREDIT_MTU_MPS || mps < MIN_LE_CREDIT_MTU_MPS {
+            EcredResult::UnacceptableParameters
+        } else if total_requested > MAX_REQUESTED_CIDS || free_slots < total_requested {
+            // Per the ECRED rules, a non-success result must leave no channel established at all,
+            // so every slot this request would need is checked for availability up front instead
+            // of allocating channels one at a time and rolling back on a later failure.
+            EcredResult::NoResources
+        } else {
+            EcredResult::Success
+        };
+
+        let mut dest_cids = [0u16; MAX_REQUESTED_CIDS];
+        if overall_result == EcredResult::Success {
+            for i in 0..num_cids {
+                let local_cid = self
+                    .state
+                    .alloc_local_cid()
+                    .expect("free_slots >= total_requested was checked above");
+                let slot = self.state.free_slot().expect("alloc_local_cid reserved a slot");
+                *slot = Some(CocChannel {
+                    local_cid,
+                    peer_cid: source_cids[i],
+                    peer_mtu: mtu,
+                    peer_mps: mps,
+                    peer_credits: initial_credits,
+                    local_mtu: OUR_MTU,
+                    local_mps: OUR_MPS,
+                    eatt: true,
+                    pending_identifier: None,
+                    rx: SduReassembly::new(),
+                });
+                dest_cids[i] = local_cid;
+            }
+        }
+
+        // On failure the destination-CID list must still have one (zeroed) entry per source CID
+        // the peer sent, even if that's more than we could have stored locally.
+        let response_cid_count = if overall_result == EcredResult::Success {
+            num_cids
+        } else {
+            total_requested
+        };
+
+        self.send_signaling(identifier, 0x18, |writer| {
+            writer.write_u16_le(OUR_MTU)?;
+            writer.write_u16_le(OUR_MPS)?;
+            writer.write_u16_le(OUR_INITIAL_CREDITS)?;
+            writer.write_u16_le(overall_result as u16)?;
+            for i in 0..response_cid_count {
+                let cid = if overall_result == EcredResult::Success {
+                    dest_cids[i]
+                } else {
+                    0
+                };
+                writer.write_u16_le(cid)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Handles `L2CAP_ECRED_RECONFIGURE_REQ`, renegotiating MTU/MPS on a list of channels
+    /// identified by our local CIDs. Per the L2CAP reconfiguration rules, the MTU may only grow;
+    /// shrinking it is rejected for every named channel.
+    fn handle_ecred_reconfigure_req(&mut self, identifier: u8, data: &[u8]) -> Result<(), Error> {
+        let mut reader = ByteReader::new(data);
+        let mtu = reader.read_u16_le()?;
+        let mps = reader.read_u16_le()?;
+
+        let mut cids = [0u16; MAX_CHANNELS];
+        let mut num_cids = 0;
+        while reader.bytes_left() >= 2 && num_cids < MAX_CHANNELS {
+            cids[num_cids] = reader.read_u16_le()?;
+            num_cids += 1;
+        }
+
+        let mut result = EcredReconfigureResult::Success;
+        for &cid in &cids[..num_cids] {
+            match self.state.channel_mut(cid) {
+                Some(channel) if mtu < channel.local_mtu => {
+                    result = EcredReconfigureResult::MtuReductionNotAllowed;
+                }
+                Some(_) => {}
+                None => result = EcredReconfigureResult::InvalidCid,
+            }
+        }
+
+        if result == EcredReconfigureResult::Success {
+            for &cid in &cids[..num_cids] {
+                if let Some(channel) = self.state.channel_mut(cid) {
+                    channel.local_mtu = mtu;
+                    channel.local_mps = mps;
+                }
+            }
+        }
+
+        self.send_signaling(identifier, 0x1a, |writer| writer.write_u16_le(result as u16))
+    }
+
+    /// Handles `LE_CREDIT_BASED_CONNECTION_RSP`, correlating it with the channel that is waiting
+    /// for it by the signaling identifier `open_channel` sent the matching `_REQ` with, not merely
+    /// by scanning for a channel with no peer CID yet — otherwise a second `open_channel` call
+    /// started before the first one's response arrives would be indistinguishable from it.
+    fn handle_credit_based_connection_rsp(&mut self, identifier: u8, data: &[u8]) -> Result<(), Error> {
+        let mut reader = ByteReader::new(data);
+        let dest_cid = reader.read_u16_le()?;
+        let mtu = reader.read_u16_le()?;
+        let mps = reader.read_u16_le()?;
+        let initial_credits = reader.read_u16_le()?;
+        let result = reader.read_u16_le()?;
+
+        let channel = self
+            .state
+            .channels
+            .iter_mut()
+            .flatten()
+            .find(|c| c.pending_identifier == Some(identifier));
+        if let Some(channel) = channel {
+            channel.pending_identifier = None;
+            let acceptable =
+                mtu >= MIN_LE_CREDIT_MTU_MPS && mps >= MIN_LE_CREDIT_MTU_MPS;
+            if result == ConnectionResult::Success as u16 && acceptable {
+                channel.peer_cid = dest_cid;
+                channel.peer_mtu = mtu;
+                channel.peer_mps = mps;
+                channel.peer_credits = initial_credits;
+            } else {
+                let local_cid = channel.local_cid;
+                for slot in &mut self.state.channels {
+                    if matches!(slot, Some(c) if c.local_cid == local_cid) {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens an outgoing LE Credit-Based Connection-Oriented Channel to `le_psm`.
+    ///
+    /// The returned [`ChannelHandle`] can be used for [`send_sdu`](Self::send_sdu) once the peer
+    /// has accepted the connection via `LE_CREDIT_BASED_CONNECTION_RSP`.
+    pub fn open_channel(
+        &mut self,
+        le_psm: u16,
+        mtu: u16,
+        mps: u16,
+        initial_credits: u16,
+    ) -> Result<ChannelHandle, Error> {
+        let local_cid = self.state.alloc_local_cid().ok_or(Error::NoFreeSlots)?;
+        let identifier = self.state.alloc_identifier();
+        let slot = self.state.free_slot().expect("alloc_local_cid reserved a slot");
+        *slot = Some(CocChannel {
+            local_cid,
+            peer_cid: 0,
+            peer_mtu: 0,
+            peer_mps: 0,
+            peer_credits: 0,
+            local_mtu: mtu,
+            local_mps: mps,
+            eatt: false,
+            pending_identifier: Some(identifier),
+            rx: SduReassembly::new(),
+        });
+
+        self.send_signaling(identifier, 0x14, |writer| {
+            writer.write_u16_le(le_psm)?;
+            writer.write_u16_le(local_cid)?;
+            writer.write_u16_le(mtu)?;
+            writer.write_u16_le(mps)?;
+            writer.write_u16_le(initial_credits)
+        })?;
+
+        Ok(ChannelHandle(local_cid))
+    }
+
+    fn handle_credit_based_connection_req(
+        &mut self,
+        identifier: u8,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let mut reader = ByteReader::new(data);
+        let _le_psm = reader.read_u16_le()?;
+        let source_cid = reader.read_u16_le()?;
+        let mtu = reader.read_u16_le()?;
+        let mps = reader.read_u16_le()?;
+        let initial_credits = reader.read_u16_le()?;
+
+        const OUR_MTU: u16 = 256;
+        const OUR_MPS: u16 = 128;
+        const OUR_INITIAL_CREDITS: u16 = 8;
+
+        let (dest_cid, result) = if mtu < MIN_LE_CREDIT_MTU_MPS || mps < MIN_LE_CREDIT_MTU_MPS {
+            (0, ConnectionResult::UnacceptableParameters)
+        } else {
+            match self.state.alloc_local_cid() {
+                Some(local_cid) => {
+                    let slot = self.state.free_slot().expect("alloc_local_cid reserved a slot");
+                    *slot = Some(CocChannel {
+                        local_cid,
+                        peer_cid: source_cid,
+                        peer_mtu: mtu,
+                        peer_mps: mps,
+                        peer_credits: initial_credits,
+                        local_mtu: OUR_MTU,
+                        local_mps: OUR_MPS,
+                        eatt: false,
+                        pending_identifier: None,
+                        rx: SduReassembly::new(),
+                    });
+                    (local_cid, ConnectionResult::Success)
+                }
+                None => (0, ConnectionResult::NoResources),
+            }
+        };
+
+        self.send_signaling(identifier, 0x15, |writer| {
+            writer.write_u16_le(dest_cid)?;
+            writer.write_u16_le(OUR_MTU)?;
+            writer.write_u16_le(OUR_MPS)?;
+            writer.write_u16_le(OUR_INITIAL_CREDITS)?;
+            writer.write_u16_le(result as u16)
+        })
+    }
+
+    /// Replenishes the peer's credit count so it may send us more K-frames, via
+    /// `LE_FLOW_CONTROL_CREDIT`.
+    pub fn replenish_credits(
+        &mut self,
+        handle: ChannelHandle,
+        credits: u16,
+    ) -> Result<(), Error> {
+        let cid = self
+            .state
+            .channel_mut(handle.0)
+            .ok_or(Error::InvalidState)?
+            .local_cid;
+        let identifier = self.state.alloc_identifier();
+        self.send_signaling(identifier, 0x16, |writer| {
+            writer.write_u16_le(cid)?;
+            writer.write_u16_le(credits)
+        })
+    }
+
+    /// Reads the next fully-reassembled SDU received on `handle`, if any.
+    pub fn take_sdu(&mut self, handle: ChannelHandle) -> Option<&[u8]> {
+        self.state.channel_mut(handle.0)?.rx.take()
+    }
+
+    /// Sends `sdu` over `handle`, splitting it into MPS-sized K-frames and consuming one peer
+    /// credit per frame.
+    ///
+    /// Returns `Error::NoFreeSlots` if not enough credits are available to send the whole SDU
+    /// without stalling; no partial K-frames are sent in that case.
+    pub fn send_sdu(&mut self, handle: ChannelHandle, sdu: &[u8]) -> Result<(), Error> {
+        let (peer_cid, peer_mtu, peer_mps, peer_credits) = {
+            let channel = self.state.channel_mut(handle.0).ok_or(Error::InvalidState)?;
+            (
+                channel.peer_cid,
+                channel.peer_mtu,
+                channel.peer_mps,
+                channel.peer_credits,
+            )
+        };
+
+        if sdu.len() as u16 > peer_mtu {
+            return Err(Error::InvalidValue);
+        }
+
+        let frames_needed = kframe_count(sdu.len(), peer_mps as usize);
+        if frames_needed > peer_credits as usize {
+            return Err(Error::NoFreeSlots);
+        }
+
+        let mut offset = 0usize;
+        let mut first = true;
+        while offset < sdu.len() || first {
+            let header_len = if first { 2 } else { 0 };
+            let room = peer_mps as usize - header_len;
+            let chunk_len = room.min(sdu.len() - offset);
+            let chunk = &sdu[offset..offset + chunk_len];
+
+            let payload_len = header_len + chunk_len;
+            self.producer.produce_with((4 + payload_len) as u8, |writer| {
+                writer.write_u16_le(payload_len as u16)?;
+                writer.write_u16_le(peer_cid)?;
+                if first {
+                    writer.write_u16_le(sdu.len() as u16)?;
+                }
+                writer.write_slice(chunk)?;
+                Ok(Llid::DataStart)
+            })?;
+
+            self.state.channel_mut(handle.0).unwrap().peer_credits -= 1;
+            offset += chunk_len;
+            first = false;
+            if sdu.is_empty() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn send_signaling(
+        &mut self,
+        identifier: u8,
+        code: u8,
+        f: impl FnOnce(&mut ByteWriter<'_>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        // Signaling responses in this module are all small, fixed-size (or fixed-upper-bound,
+        // for ECRED's per-CID lists) PDUs.
+        let mut scratch = [0u8; 32];
+        let payload_len = {
+            let mut writer = ByteWriter::new(&mut scratch);
+            f(&mut writer)?;
+            writer.len()
+        };
+
+        self.producer.produce_with((4 + 4 + payload_len) as u8, |writer| {
+            writer.write_u16_le((4 + payload_len) as u16)?;
+            writer.write_u16_le(SIGNALING_CID)?;
+            writer.write_u8(code)?;
+            writer.write_u8(identifier)?;
+            writer.write_u16_le(payload_len as u16)?;
+            writer.write_slice(&scratch[..payload_len])?;
+            Ok(Llid::DataStart)
+        })
+    }
+}
+
+/// Result codes used by `LE_CREDIT_BASED_CONNECTION_RSP`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ConnectionResult {
+    Success = 0x0000,
+    NoResources = 0x0004,
+    UnacceptableParameters = 0x000d,
+}
+
+/// Result codes used by `L2CAP_ECRED_CONN_RSP`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum EcredResult {
+    Success = 0x0000,
+    NoResources = 0x0004,
+    UnacceptableParameters = 0x000d,
+}
+
+/// Result codes used by `L2CAP_ECRED_RECONFIGURE_RSP`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum EcredReconfigureResult {
+    Success = 0x0000,
+    InvalidCid = 0x0001,
+    MtuReductionNotAllowed = 0x0002,
+}
+
+fn kframe_count(sdu_len: usize, mps: usize) -> usize {
+    if sdu_len + 2 <= mps {
+        return 1;
+    }
+    let first = mps - 2;
+    1 + ((sdu_len - first) + mps - 1) / mps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kframe_count_fits_in_one_frame() {
+        assert_eq!(kframe_count(0, 23), 1);
+        assert_eq!(kframe_count(10, 23), 1);
+        assert_eq!(kframe_count(21, 23), 1);
+    }
+
+    #[test]
+    fn kframe_count_splits_across_frames() {
+        // 25-byte SDU: first frame carries mps - 2 = 21 bytes, second carries the remaining 4.
+        assert_eq!(kframe_count(25, 23), 2);
+        // 44-byte SDU: remaining 23 bytes after the first frame fill the second exactly.
+        assert_eq!(kframe_count(44, 23), 2);
+        // 45-byte SDU: remaining 24 bytes need a third frame.
+        assert_eq!(kframe_count(45, 23), 3);
+    }
+
+    #[test]
+    fn sdu_reassembly_single_frame() {
+        let mut rx = SduReassembly::new();
+        let kframe = [3, 0, 1, 2, 3]; // sdu_len = 3 (LE), then the 3-byte payload.
+
+        rx.feed(&kframe, 23).unwrap();
+        assert_eq!(rx.take(), Some(&[1, 2, 3][..]));
+        // Already taken; nothing left until the next SDU starts.
+        assert_eq!(rx.take(), None);
+    }
+
+    #[test]
+    fn sdu_reassembly_rejects_sdu_larger_than_local_mtu() {
+        let mut rx = SduReassembly::new();
+        let kframe = [100, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]; // sdu_len = 100 (LE), local_mtu = 23.
+
+        assert!(rx.feed(&kframe, 23).is_err());
+    }
+
+    #[test]
+    fn sdu_reassembly_across_multiple_kframes() {
+        let mut rx = SduReassembly::new();
+        let first = [5, 0, 1, 2, 3]; // sdu_len = 5 (LE), then 3 of the 5 payload bytes.
+        rx.feed(&first, 23).unwrap();
+        // Not complete yet - only 3 of 5 expected bytes arrived.
+        assert_eq!(rx.take(), None);
+
+        rx.feed(&[4, 5], 23).unwrap();
+        assert_eq!(rx.take(), Some(&[1, 2, 3, 4, 5][..]));
+    }
+
+    struct NullMapper;
+    impl ChannelMapper for NullMapper {
+        fn on_att_pdu(&mut self, _bearer: AttBearer, _payload: &[u8]) {}
+    }
+
+    /// A [`Producer`] that records the last PDU written to it, for inspection by tests.
+    struct RecordingProducer {
+        buf: [u8; 64],
+        len: usize,
+    }
+
+    impl RecordingProducer {
+        fn new() -> Self {
+            Self { buf: [0; 64], len: 0 }
+        }
+
+        /// Decodes the ATT PDU from the last frame handed to `produce_with`, skipping the 4-byte
+        /// L2CAP basic header (`length`, `cid`) that `send_att_pdu_raw` writes ahead of it.
+        fn last_att_pdu(&self) -> AttPdu {
+            let mut reader = ByteReader::new(&self.buf[4..self.len]);
+            AttPdu::from_bytes(&mut reader).unwrap()
+        }
+    }
+
+    impl Producer for RecordingProducer {
+        fn produce_with(
+            &mut self,
+            _size: u8,
+            f: impl FnOnce(&mut ByteWriter<'_>) -> Result<Llid, Error>,
+        ) -> Result<(), Error> {
+            let mut writer = ByteWriter::new(&mut self.buf);
+            f(&mut writer)?;
+            self.len = writer.len();
+            Ok(())
+        }
+    }
+
+    /// Encodes an `ATT_EXCHANGE_MTU_REQ` with the given `client_rx_mtu` into a fixed-size buffer,
+    /// returning it together with the number of bytes actually written.
+    fn exchange_mtu_request(client_rx_mtu: u16) -> ([u8; 8], usize) {
+        let mut buf = [0u8; 8];
+        let mut writer = ByteWriter::new(&mut buf);
+        AttPdu::ExchangeMtuRequest { client_rx_mtu }
+            .to_bytes(&mut writer)
+            .unwrap();
+        let len = writer.len();
+        (buf, len)
+    }
+
+    #[test]
+    fn att_mtu_defaults_before_exchange() {
+        let state = L2CAPState::new(NullMapper);
+        assert_eq!(state.att_mtu(), att::ATT_DEFAULT_MTU);
+    }
+
+    #[test]
+    fn exchange_mtu_clamps_the_client_value_to_att_max_mtu() {
+        let mut state = L2CAPState::new(NullMapper);
+        let mut producer = RecordingProducer::new();
+        let (buf, len) = exchange_mtu_request(500);
+
+        state.tx(&mut producer).handle_att_pdu(&buf[..len]).unwrap();
+
+        assert_eq!(state.att_mtu(), att::ATT_MAX_MTU);
+        assert_eq!(
+            producer.last_att_pdu(),
+            AttPdu::ExchangeMtuResponse {
+                server_rx_mtu: att::ATT_MAX_MTU
+            }
+        );
+    }
+
+    #[test]
+    fn exchange_mtu_adopts_a_client_value_within_range() {
+        let mut state = L2CAPState::new(NullMapper);
+        let mut producer = RecordingProducer::new();
+        let (buf, len) = exchange_mtu_request(50);
+
+        state.tx(&mut producer).handle_att_pdu(&buf[..len]).unwrap();
+
+        assert_eq!(state.att_mtu(), 50);
+    }
+
+    #[test]
+    fn a_second_exchange_mtu_on_the_same_bearer_is_rejected() {
+        let mut state = L2CAPState::new(NullMapper);
+        let mut producer = RecordingProducer::new();
+
+        let (first, first_len) = exchange_mtu_request(100);
+        state
+            .tx(&mut producer)
+            .handle_att_pdu(&first[..first_len])
+            .unwrap();
+        assert_eq!(state.att_mtu(), 100);
+
+        let (second, second_len) = exchange_mtu_request(50);
+        state
+            .tx(&mut producer)
+            .handle_att_pdu(&second[..second_len])
+            .unwrap();
+
+        // The second attempt is rejected, not applied.
+        assert_eq!(state.att_mtu(), 100);
+        assert_eq!(
+            producer.last_att_pdu(),
+            AttPdu::ErrorResponse {
+                request_opcode: att::OPCODE_EXCHANGE_MTU_REQUEST,
+                attribute_handle: 0x0000,
+                error_code: att::ERROR_REQUEST_NOT_SUPPORTED,
+            }
+        );
+    }
+}
@@ -0,0 +1,23 @@
+//! Rubble: a pure-Rust Bluetooth Low Energy stack for embedded devices.
+
+#![no_std]
+
+pub mod att;
+pub mod bytes;
+pub mod config;
+mod error;
+pub mod gatt;
+pub mod l2cap;
+pub mod link;
+pub mod utils;
+
+pub use crate::error::Error;
+
+/// Includes the GATT attribute table generated by `rubble-codegen`'s `codegen::Builder` into the
+/// crate calling this macro.
+#[macro_export]
+macro_rules! include_attributes {
+    () => {
+        include!(concat!(env!("OUT_DIR"), "/rubble_codegen.rs"));
+    };
+}
@@ -0,0 +1,47 @@
+//! Data Channel PDU types.
+
+use crate::{
+    bytes::{ByteReader, FromBytes},
+    link::llcp::ControlPdu,
+};
+
+/// The `LLID` field of a Data Channel PDU header, identifying the kind of payload it carries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Llid {
+    /// Continuation of an L2CAP message, or an empty PDU.
+    DataCont,
+    /// Start (or all) of an L2CAP message.
+    DataStart,
+    /// LL Control PDU.
+    Control,
+}
+
+/// A Data Channel PDU, parsed just enough to dispatch it to the right part of the stack.
+#[derive(Debug)]
+pub enum Pdu<'a> {
+    /// An LL Control PDU, carrying an as-yet-unparsed LLCP control opcode and payload.
+    Control { data: ControlData<'a> },
+    /// The first (or only) fragment of an L2CAP message.
+    DataStart { message: &'a [u8] },
+    /// A continuation fragment of an L2CAP message.
+    DataCont { message: &'a [u8] },
+}
+
+/// The raw payload of an LL Control PDU, lazily parsed into a [`ControlPdu`].
+///
+/// Parsing is deferred since most control PDUs are handled by realtime code and never need to be
+/// decoded by the non-realtime [`Responder`](crate::link::responder::Responder).
+#[derive(Debug)]
+pub struct ControlData<'a>(&'a [u8]);
+
+impl<'a> ControlData<'a> {
+    pub fn new(raw: &'a [u8]) -> Self {
+        Self(raw)
+    }
+
+    /// Parses the wrapped bytes into a [`ControlPdu`].
+    pub fn read(&self) -> ControlPdu<'a> {
+        let mut reader = ByteReader::new(self.0);
+        ControlPdu::from_bytes(&mut reader).expect("malformed LL Control PDU")
+    }
+}
@@ -0,0 +1,287 @@
+//! Link Layer Control Protocol (LLCP) control PDUs.
+
+use crate::{
+    bytes::{ByteReader, ByteWriter, FromBytes, ToBytes},
+    Error,
+};
+
+/// HCI error code used as the `reason` in `LL_REJECT_EXT_IND` when connection parameters fall
+/// outside the limits the spec allows, or are otherwise unacceptable to the local Link Layer.
+pub const REASON_UNACCEPTABLE_CONN_PARAMS: u8 = 0x3b;
+
+/// HCI error code used as the `reason` in `LL_REJECT_EXT_IND` when the rejected opcode collides
+/// with an LLCP procedure that is already in progress.
+pub const REASON_LL_PROCEDURE_COLLISION: u8 = 0x23;
+
+/// A parsed LL Control PDU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlPdu<'a> {
+    /// `LL_FEATURE_REQ` - handled by the realtime Link Layer code, never reaches the `Responder`.
+    FeatureReq { features: u64 },
+    /// `LL_VERSION_IND` - handled by the realtime Link Layer code, never reaches the `Responder`.
+    VersionInd {
+        version: u8,
+        company_id: u16,
+        subversion: u16,
+    },
+    /// `LL_CONNECTION_PARAM_REQ` - request to change the connection's parameters.
+    ConnectionParamReq(ConnectionParamRequest),
+    /// `LL_CONNECTION_PARAM_RSP` - accepts a previously requested connection parameter change.
+    ConnectionParamRsp(ConnectionParamRequest),
+    /// `LL_REJECT_EXT_IND` - rejects a procedure started by the peer, naming the opcode that was
+    /// rejected and a reason code.
+    RejectExtInd { rejected_opcode: u8, reason: u8 },
+    /// `LL_LENGTH_REQ` - proposes RX/TX payload and time limits for the Data Length Extension.
+    LengthReq {
+        max_rx_octets: u16,
+        max_rx_time: u16,
+        max_tx_octets: u16,
+        max_tx_time: u16,
+    },
+    /// `LL_LENGTH_RSP` - answers an `LL_LENGTH_REQ` with the limits that will actually be used.
+    LengthRsp {
+        max_rx_octets: u16,
+        max_rx_time: u16,
+        max_tx_octets: u16,
+        max_tx_time: u16,
+    },
+    /// `LL_UNKNOWN_RSP` - sent in response to a control PDU we don't understand or don't support.
+    UnknownRsp { unknown_type: u8 },
+    /// An opcode this version of Rubble does not parse any further.
+    Unknown { opcode: u8, payload: &'a [u8] },
+}
+
+impl<'a> ControlPdu<'a> {
+    /// The LLCP opcode identifying this PDU.
+    pub fn opcode(&self) -> u8 {
+        match self {
+            ControlPdu::FeatureReq { .. } => 0x08,
+            ControlPdu::VersionInd { .. } => 0x0c,
+            ControlPdu::ConnectionParamReq(_) => 0x0f,
+            ControlPdu::ConnectionParamRsp(_) => 0x10,
+            ControlPdu::RejectExtInd { .. } => 0x11,
+            ControlPdu::LengthReq { .. } => 0x14,
+            ControlPdu::LengthRsp { .. } => 0x15,
+            ControlPdu::UnknownRsp { .. } => 0x07,
+            ControlPdu::Unknown { opcode, .. } => *opcode,
+        }
+    }
+}
+
+impl<'a> FromBytes<'a> for ControlPdu<'a> {
+    fn from_bytes(reader: &mut ByteReader<'a>) -> Result<Self, Error> {
+        let opcode = reader.read_u8()?;
+        Ok(match opcode {
+            0x07 => ControlPdu::UnknownRsp {
+                unknown_type: reader.read_u8()?,
+            },
+            0x08 => {
+                let mut features = 0u64;
+                for i in 0..8 {
+                    features |= (reader.read_u8()? as u64) << (i * 8);
+                }
+                ControlPdu::FeatureReq { features }
+            }
+            0x0c => ControlPdu::VersionInd {
+                version: reader.read_u8()?,
+                company_id: reader.read_u16_le()?,
+                subversion: reader.read_u16_le()?,
+            },
+            0x0f => ControlPdu::ConnectionParamReq(ConnectionParamRequest::from_bytes(reader)?),
+            0x10 => ControlPdu::ConnectionParamRsp(ConnectionParamRequest::from_bytes(reader)?),
+            0x11 => ControlPdu::RejectExtInd {
+                rejected_opcode: reader.read_u8()?,
+                reason: reader.read_u8()?,
+            },
+            0x14 => ControlPdu::LengthReq {
+                max_rx_octets: reader.read_u16_le()?,
+                max_rx_time: reader.read_u16_le()?,
+                max_tx_octets: reader.read_u16_le()?,
+                max_tx_time: reader.read_u16_le()?,
+            },
+            0x15 => ControlPdu::LengthRsp {
+                max_rx_octets: reader.read_u16_le()?,
+                max_rx_time: reader.read_u16_le()?,
+                max_tx_octets: reader.read_u16_le()?,
+                max_tx_time: reader.read_u16_le()?,
+            },
+            opcode => ControlPdu::Unknown {
+                opcode,
+                payload: reader.rest(),
+            },
+        })
+    }
+}
+
+impl<'a> ToBytes for ControlPdu<'a> {
+    fn to_bytes(&self, writer: &mut ByteWriter<'_>) -> Result<(), Error> {
+        writer.write_u8(self.opcode())?;
+        match self {
+            ControlPdu::UnknownRsp { unknown_type } => writer.write_u8(*unknown_type),
+            ControlPdu::FeatureReq { features } => {
+                for i in 0..8 {
+                    writer.write_u8((features >> (i * 8)) as u8)?;
+                }
+                Ok(())
+            }
+            ControlPdu::VersionInd {
+                version,
+                company_id,
+                subversion,
+            } => {
+                writer.write_u8(*version)?;
+                writer.write_u16_le(*company_id)?;
+                writer.write_u16_le(*subversion)
+            }
+            ControlPdu::ConnectionParamReq(params) | ControlPdu::ConnectionParamRsp(params) => {
+                params.to_bytes(writer)
+            }
+            ControlPdu::RejectExtInd {
+                rejected_opcode,
+                reason,
+            } => {
+                writer.write_u8(*rejected_opcode)?;
+                writer.write_u8(*reason)
+            }
+            ControlPdu::LengthReq {
+                max_rx_octets,
+                max_rx_time,
+                max_tx_octets,
+                max_tx_time,
+            }
+            | ControlPdu::LengthRsp {
+                max_rx_octets,
+                max_rx_time,
+                max_tx_octets,
+                max_tx_time,
+            } => {
+                writer.write_u16_le(*max_rx_octets)?;
+                writer.write_u16_le(*max_rx_time)?;
+                writer.write_u16_le(*max_tx_octets)?;
+                writer.write_u16_le(*max_tx_time)
+            }
+            ControlPdu::Unknown { payload, .. } => writer.write_slice(payload),
+        }
+    }
+
+    fn encoded_size(&self) -> u8 {
+        1 + match self {
+            ControlPdu::UnknownRsp { .. } => 1,
+            ControlPdu::FeatureReq { .. } => 8,
+            ControlPdu::VersionInd { .. } => 5,
+            ControlPdu::ConnectionParamReq(params) | ControlPdu::ConnectionParamRsp(params) => {
+                params.encoded_size()
+            }
+            ControlPdu::RejectExtInd { .. } => 2,
+            ControlPdu::LengthReq { .. } | ControlPdu::LengthRsp { .. } => 8,
+            ControlPdu::Unknown { payload, .. } => payload.len() as u8,
+        }
+    }
+}
+
+/// The body of `LL_CONNECTION_PARAM_REQ`/`LL_CONNECTION_PARAM_RSP`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionParamRequest {
+    pub interval_min: u16,
+    pub interval_max: u16,
+    pub latency: u16,
+    pub timeout: u16,
+}
+
+impl ConnectionParamRequest {
+    /// Checks the connection parameters against the limits allowed by the Bluetooth spec.
+    ///
+    /// This does not know anything about the local controller's *preferences*, only the
+    /// hard limits imposed by the spec (connSupervisionTimeout, connLatency, interval ranges).
+    pub fn is_acceptable(&self) -> bool {
+        let interval_ok = (0x0006..=0x0c80).contains(&self.interval_min)
+            && self.interval_min <= self.interval_max
+            && self.interval_max <= 0x0c80;
+        let latency_ok = self.latency <= 0x01f3;
+        let timeout_ok = (0x000a..=0x0c80).contains(&self.timeout);
+        // connSupervisionTimeout (in 10ms units) must be large enough to survive `connLatency`
+        // missed connection events, each `interval_max` (in 1.25ms units) long: timeout_ms >
+        // (1 + latency) * interval_max_ms * 2, i.e. `* 5 / 4` to convert interval_max to
+        // milliseconds and another `* 2` for the required margin, for `* 5 / 2` overall.
+        let timeout_covers_latency =
+            u32::from(self.timeout) * 10 > (1 + u32::from(self.latency)) * u32::from(self.interval_max) * 5 / 2;
+        interval_ok && latency_ok && timeout_ok && timeout_covers_latency
+    }
+}
+
+impl<'a> FromBytes<'a> for ConnectionParamRequest {
+    fn from_bytes(reader: &mut ByteReader<'a>) -> Result<Self, Error> {
+        Ok(Self {
+            interval_min: reader.read_u16_le()?,
+            interval_max: reader.read_u16_le()?,
+            latency: reader.read_u16_le()?,
+            timeout: reader.read_u16_le()?,
+        })
+    }
+}
+
+impl ToBytes for ConnectionParamRequest {
+    fn to_bytes(&self, writer: &mut ByteWriter<'_>) -> Result<(), Error> {
+        writer.write_u16_le(self.interval_min)?;
+        writer.write_u16_le(self.interval_max)?;
+        writer.write_u16_le(self.latency)?;
+        writer.write_u16_le(self.timeout)
+    }
+
+    fn encoded_size(&self) -> u8 {
+        8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(interval_min: u16, interval_max: u16, latency: u16, timeout: u16) -> ConnectionParamRequest {
+        ConnectionParamRequest {
+            interval_min,
+            interval_max,
+            latency,
+            timeout,
+        }
+    }
+
+    #[test]
+    fn is_acceptable_accepts_a_timeout_with_enough_margin() {
+        // interval_max = 80 (100ms), latency = 0: timeout must exceed 100ms * 2 = 200ms, i.e.
+        // timeout > 20 (10ms units).
+        assert!(params(80, 80, 0, 21).is_acceptable());
+        assert!(!params(80, 80, 0, 20).is_acceptable());
+    }
+
+    #[test]
+    fn is_acceptable_scales_the_required_timeout_with_latency() {
+        // latency = 1: timeout must exceed (1 + 1) * 100ms * 2 = 400ms, i.e. timeout > 40.
+        assert!(params(80, 80, 1, 41).is_acceptable());
+        assert!(!params(80, 80, 1, 40).is_acceptable());
+    }
+
+    #[test]
+    fn is_acceptable_rejects_a_timeout_that_cannot_survive_missed_events() {
+        // A timeout that only covers half the required margin (the bug this regresses: treating
+        // the margin as `interval_max_ms * 1` instead of `* 2`) must be rejected.
+        assert!(!params(80, 80, 0, 15).is_acceptable());
+    }
+
+    #[test]
+    fn is_acceptable_rejects_out_of_range_interval() {
+        assert!(!params(0x0005, 0x0005, 0, 100).is_acceptable());
+        assert!(!params(100, 50, 0, 100).is_acceptable());
+    }
+
+    #[test]
+    fn is_acceptable_rejects_out_of_range_latency() {
+        assert!(!params(80, 80, 0x01f4, 10000).is_acceptable());
+    }
+
+    #[test]
+    fn is_acceptable_rejects_out_of_range_timeout() {
+        assert!(!params(80, 80, 0, 0x0009).is_acceptable());
+        assert!(!params(80, 80, 0, 0x0c81).is_acceptable());
+    }
+}
@@ -0,0 +1,107 @@
+//! The Link Layer: LLCP control procedures and the data channel packet processor.
+
+pub mod data;
+pub mod llcp;
+pub mod queue;
+pub mod responder;
+
+use core::marker::PhantomData;
+
+use crate::config::Config;
+
+/// The maximum payload octets this implementation supports via the Data Length Extension.
+///
+/// The Bluetooth spec caps `max_tx_octets`/`max_rx_octets` at 251.
+const MAX_SUPPORTED_OCTETS: u16 = 251;
+
+/// An LLCP control procedure that is in progress and blocks any other procedure from being
+/// started until it completes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Procedure {
+    /// The *Connection Parameters Request Procedure* (`LL_CONNECTION_PARAM_REQ`/`_RSP`).
+    ConnectionParameterUpdate,
+}
+
+/// The Data Length Extension parameters negotiated for a connection via
+/// `LL_LENGTH_REQ`/`LL_LENGTH_RSP`.
+#[derive(Debug, Clone, Copy)]
+pub struct DataLength {
+    /// Maximum number of payload octets in a Data Channel PDU we will transmit.
+    pub max_tx_octets: u16,
+    /// Maximum number of payload octets in a Data Channel PDU we will accept.
+    pub max_rx_octets: u16,
+}
+
+impl Default for DataLength {
+    /// The default, pre-Data-Length-Extension payload size.
+    fn default() -> Self {
+        Self {
+            max_tx_octets: 27,
+            max_rx_octets: 27,
+        }
+    }
+}
+
+impl DataLength {
+    /// Computes the `DataLength` to apply for an incoming `LL_LENGTH_REQ`, clamping the peer's
+    /// proposal against [`MAX_SUPPORTED_OCTETS`].
+    ///
+    /// `peer_max_rx_octets`/`peer_max_tx_octets` are the peer's own fields from the request, named
+    /// from the peer's perspective: `peer_max_rx_octets` is what the peer can accept from us, which
+    /// is what bounds our `max_tx_octets`, and vice versa.
+    pub(crate) fn from_peer_length_req(peer_max_rx_octets: u16, peer_max_tx_octets: u16) -> Self {
+        Self {
+            max_tx_octets: peer_max_rx_octets.min(MAX_SUPPORTED_OCTETS),
+            max_rx_octets: peer_max_tx_octets.min(MAX_SUPPORTED_OCTETS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn length_req_is_applied_without_swapping_rx_and_tx() {
+        // Peer can accept up to 100 octets from us, and will send us up to 200.
+        let data_length = DataLength::from_peer_length_req(100, 200);
+        assert_eq!(data_length.max_tx_octets, 100);
+        assert_eq!(data_length.max_rx_octets, 200);
+    }
+
+    #[test]
+    fn length_req_is_clamped_to_max_supported_octets() {
+        let data_length = DataLength::from_peer_length_req(300, 300);
+        assert_eq!(data_length.max_tx_octets, MAX_SUPPORTED_OCTETS);
+        assert_eq!(data_length.max_rx_octets, MAX_SUPPORTED_OCTETS);
+    }
+}
+
+/// State tracked for one established Link Layer connection.
+pub struct Connection<C: Config> {
+    /// The LLCP procedure currently in progress, if any.
+    pub(crate) active_procedure: Option<Procedure>,
+    /// The Data Length Extension parameters currently in effect.
+    pub(crate) data_length: DataLength,
+    _config: PhantomData<C>,
+}
+
+impl<C: Config> Connection<C> {
+    pub fn new() -> Self {
+        Self {
+            active_procedure: None,
+            data_length: DataLength::default(),
+            _config: PhantomData,
+        }
+    }
+
+    /// The Data Length Extension parameters currently in effect for this connection.
+    pub fn data_length(&self) -> DataLength {
+        self.data_length
+    }
+
+    /// Returns `true` if an LLCP procedure is currently in progress.
+    pub fn llcp_busy(&self) -> bool {
+        self.active_procedure.is_some()
+    }
+}
@@ -0,0 +1,76 @@
+//! Packet queues connecting the realtime Link Layer to the non-realtime [`Responder`].
+//!
+//! [`Responder`]: crate::link::responder::Responder
+
+use crate::{bytes::ByteWriter, link::data::{Llid, Pdu}, Error};
+
+/// Per-PDU metadata handed to a [`Consumer`] alongside the parsed [`Pdu`].
+#[derive(Debug, Clone, Copy)]
+pub struct Header {
+    pub llid: Llid,
+    pub length: u8,
+}
+
+/// Tells a [`Consumer`] whether the PDU it just inspected should be removed from the queue.
+///
+/// Wraps the result `T` of processing a PDU. Use [`Consume::on_success`] when the PDU should only
+/// be removed once its processing fully succeeds (eg. because the response could be enqueued),
+/// and [`Consume::always`] when it must be removed regardless of the outcome.
+#[derive(Debug)]
+pub enum Consume<T> {
+    Always(T),
+    OnSuccess(T),
+}
+
+impl Consume<Result<(), Error>> {
+    pub fn always(result: Result<(), Error>) -> Self {
+        Consume::Always(result)
+    }
+
+    pub fn on_success(result: Result<(), Error>) -> Self {
+        Consume::OnSuccess(result)
+    }
+
+    /// Returns `true` if the PDU this value corresponds to should be removed from the queue.
+    pub fn should_consume(&self) -> bool {
+        match self {
+            Consume::Always(_) => true,
+            Consume::OnSuccess(result) => result.is_ok(),
+        }
+    }
+
+    pub fn into_result(self) -> Result<(), Error> {
+        match self {
+            Consume::Always(result) | Consume::OnSuccess(result) => result,
+        }
+    }
+}
+
+/// Consumer end of a packet queue, used to read incoming Data Channel PDUs.
+pub trait Consumer {
+    /// Returns `true` if a PDU is available to be read.
+    fn has_data(&self) -> bool;
+
+    /// Inspects the next PDU in the queue without necessarily removing it.
+    ///
+    /// `f` is called with the PDU's [`Header`] and parsed [`Pdu`], and decides via the returned
+    /// [`Consume`] whether the PDU should be removed from the queue. Returns `Error::Eof` if the
+    /// queue is empty.
+    fn consume_pdu_with(
+        &mut self,
+        f: impl FnOnce(Header, Pdu<'_>) -> Consume<Result<(), Error>>,
+    ) -> Result<(), Error>;
+}
+
+/// Producer end of a packet queue, used to enqueue outgoing Data Channel PDUs.
+pub trait Producer {
+    /// Reserves `size` bytes in the queue and calls `f` to fill them in.
+    ///
+    /// `f` returns the [`Llid`] to tag the PDU with. Returns `Error::NoFreeSlots` if the queue
+    /// does not have enough room for `size` bytes.
+    fn produce_with(
+        &mut self,
+        size: u8,
+        f: impl FnOnce(&mut ByteWriter<'_>) -> Result<Llid, Error>,
+    ) -> Result<(), Error>;
+}
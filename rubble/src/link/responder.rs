@@ -4,14 +4,23 @@ use crate::{
     l2cap::{L2CAPState, L2CAPStateTx},
     link::{
         data::{Llid, Pdu},
-        llcp::{self, ConnectionParamRequest, ControlPdu},
+        llcp::{
+            self, ConnectionParamRequest, ControlPdu, REASON_LL_PROCEDURE_COLLISION,
+            REASON_UNACCEPTABLE_CONN_PARAMS,
+        },
         queue::{Consume, Consumer, Producer},
-        Connection,
+        Connection, DataLength, Procedure, MAX_SUPPORTED_OCTETS,
     },
     utils::HexSlice,
     Error,
 };
 
+/// `max_rx_time`/`max_tx_time` (in microseconds) we advertise in `LL_LENGTH_RSP`.
+///
+/// This is the time needed to transmit a maximum-size (251 octet) LE 1M PHY packet, per the
+/// Bluetooth Core Spec's `supportedMaxTxTime`/`supportedMaxRxTime` defaults.
+const DATA_LENGTH_MAX_TIME_US: u16 = 2120;
+
 /// Data channel packet processor.
 ///
 /// This hooks up to the Real-Time part of the LE Link Layer via a packet queue. This part can run
@@ -52,7 +61,7 @@ impl<C: Config> Responder<C> {
     /// Processes a single incoming packet in the packet queue.
     ///
     /// Returns `Error::Eof` if there are no incoming packets in the RX queue.
-    pub fn process_one(&mut self) -> Result<(), Error> {
+    pub fn process_one(&mut self, conn: &mut Connection<C>) -> Result<(), Error> {
         self.with_rx(|rx, this| {
             rx.consume_pdu_with(|_, pdu| match pdu {
                 Pdu::Control { data } => {
@@ -61,14 +70,10 @@ impl<C: Config> Responder<C> {
 
                     let pdu = data.read();
                     info!("<- LL Control PDU: {:?}", pdu);
-                    let response = match pdu {
-                        // These PDUs are handled by the real-time code:
-                        ControlPdu::FeatureReq { .. } | ControlPdu::VersionInd { .. } => {
-                            unreachable!("LLCPDU not handled by LL");
-                        }
-                        _ => ControlPdu::UnknownRsp {
-                            unknown_type: pdu.opcode(),
-                        },
+                    let response = match this.handle_control_pdu(conn, pdu) {
+                        Some(response) => response,
+                        // The PDU concluded a procedure we started; nothing to send back.
+                        None => return Consume::always(Ok(())),
                     };
                     info!("-> Response: {:?}", response);
 
@@ -93,6 +98,79 @@ impl<C: Config> Responder<C> {
         })
     }
 
+    /// Runs the full LLCP control-procedure responder for one incoming control PDU.
+    ///
+    /// Returns the PDU to send back, or `None` if the incoming PDU concluded a procedure *we*
+    /// started and needs no reply.
+    fn handle_control_pdu(
+        &mut self,
+        conn: &mut Connection<C>,
+        pdu: ControlPdu<'_>,
+    ) -> Option<ControlPdu<'static>> {
+        match pdu {
+            // These PDUs are handled by the real-time code:
+            ControlPdu::FeatureReq { .. } | ControlPdu::VersionInd { .. } => {
+                unreachable!("LLCPDU not handled by LL");
+            }
+
+            ControlPdu::LengthReq {
+                max_rx_octets,
+                max_tx_octets,
+                ..
+            } => {
+                // Apply it immediately - there's no separate "apply" step, the exchange itself is
+                // the whole procedure.
+                conn.data_length = DataLength::from_peer_length_req(max_rx_octets, max_tx_octets);
+
+                Some(ControlPdu::LengthRsp {
+                    max_rx_octets: MAX_SUPPORTED_OCTETS,
+                    max_rx_time: DATA_LENGTH_MAX_TIME_US,
+                    max_tx_octets: MAX_SUPPORTED_OCTETS,
+                    max_tx_time: DATA_LENGTH_MAX_TIME_US,
+                })
+            }
+
+            ControlPdu::ConnectionParamReq(params) => {
+                // LL_CONNECTION_PARAM_REQ's opcode, named here as it's rejected below.
+                const CONNECTION_PARAM_REQ_OPCODE: u8 = 0x0f;
+
+                if conn.llcp_busy() {
+                    // A peer cannot start a second procedure while one is already outstanding.
+                    Some(ControlPdu::RejectExtInd {
+                        rejected_opcode: CONNECTION_PARAM_REQ_OPCODE,
+                        reason: REASON_LL_PROCEDURE_COLLISION,
+                    })
+                } else if params.is_acceptable() {
+                    // We fully decide the new parameters within this exchange, so there's nothing
+                    // left outstanding on our end once the response has been sent.
+                    Some(ControlPdu::ConnectionParamRsp(params))
+                } else {
+                    Some(ControlPdu::RejectExtInd {
+                        rejected_opcode: CONNECTION_PARAM_REQ_OPCODE,
+                        reason: REASON_UNACCEPTABLE_CONN_PARAMS,
+                    })
+                }
+            }
+
+            // A response to a Connection Parameters Request *we* started: the procedure is done.
+            ControlPdu::ConnectionParamRsp(_) => {
+                conn.active_procedure = None;
+                None
+            }
+            ControlPdu::RejectExtInd {
+                rejected_opcode: 0x0f,
+                ..
+            } => {
+                conn.active_procedure = None;
+                None
+            }
+
+            _ => Some(ControlPdu::UnknownRsp {
+                unknown_type: pdu.opcode(),
+            }),
+        }
+    }
+
     /// Obtains access to the L2CAP instance.
     pub fn l2cap(&mut self) -> L2CAPStateTx<'_, C::ChannelMapper, C::PacketProducer> {
         self.l2cap.tx(&mut self.tx)
@@ -103,7 +181,7 @@ impl<C: Config> Responder<C> {
     /// If the link layer has already initiated an LLCP procedure and is waiting for the response,
     /// an error will be returned.
     pub fn llcp<'a>(&'a mut self, conn: &'a mut Connection<C>) -> Result<LLCPTx<'a, C>, Error> {
-        if conn.llcp_initiated {
+        if conn.llcp_busy() {
             Err(Error::InvalidState)
         } else {
             Ok(LLCPTx {
@@ -139,7 +217,7 @@ impl<'a, C: Config> LLCPTx<'a, C> {
     /// Start a *Connection Parameters Request Procedure*, requesting a change in connection
     /// parameters.
     pub fn request_conn_params(self, params: ConnectionParamRequest) -> Result<(), Error> {
-        self.link.llcp_initiated = true;
+        self.link.active_procedure = Some(Procedure::ConnectionParameterUpdate);
 
         let cpdu = llcp::ControlPdu::ConnectionParamReq(params);
         self.producer.produce_with(cpdu.encoded_size(), |writer| {
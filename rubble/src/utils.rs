@@ -0,0 +1,20 @@
+//! Miscellaneous utilities used throughout the crate.
+
+use core::fmt;
+
+/// Wraps a byte slice and implements `Debug` by formatting it as space-separated hex bytes.
+///
+/// This is used to log raw PDU contents without pulling in a hex-dumping dependency.
+pub struct HexSlice<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Debug for HexSlice<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}